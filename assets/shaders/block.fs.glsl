@@ -3,16 +3,30 @@
 out vec4 outColor;
 
 in vec2 uv;
+flat in int layer;
+in vec2 light;
+in float ambientOcclusion;
 
 uniform sampler2DArray tex;
-uniform int blockId;
+
+// Each light level is this much dimmer than the one above it
+const float LIGHT_FALLOFF = 0.8;
+// How much of the light a fully occluded corner loses
+const float AMBIENT_OCCLUSION_STRENGTH = 0.6;
+
+float brightness(float level)
+{
+  return pow(LIGHT_FALLOFF, 15.0 * (1.0 - level));
+}
 
 void main()
 {
   vec2 texCoord = uv;
   texCoord.y *= -1.0;
-  outColor = texture(tex, vec3(texCoord, blockId));
+  outColor = texture(tex, vec3(texCoord, layer));
   if (outColor.a == 0.0) {
     discard;
   }
+  outColor.rgb *= max(brightness(light.x), brightness(light.y));
+  outColor.rgb *= 1.0 - AMBIENT_OCCLUSION_STRENGTH * (1.0 - ambientOcclusion);
 }
\ No newline at end of file
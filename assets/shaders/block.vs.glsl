@@ -2,14 +2,34 @@
 
 layout (location = 0) in vec3 v_position;
 layout (location = 1) in vec2 v_uv;
+layout (location = 2) in int v_layer;
+layout (location = 3) in vec2 v_light;
+layout (location = 4) in float v_ambient_occlusion;
+
+// Animated tiles are drawn with the layer of their current frame
+const int MAX_ANIMATED_TILES = 16;
 
 uniform mat4 mvp;
+uniform int animatedTileCount;
+uniform int animatedTiles[MAX_ANIMATED_TILES];
+uniform int animatedFrames[MAX_ANIMATED_TILES];
 
 out vec2 uv;
+flat out int layer;
+out vec2 light;
+out float ambientOcclusion;
 
 void main()
 {
     vec4 position = vec4(v_position, 1.0);
     gl_Position = mvp * position;
     uv = v_uv;
+    layer = v_layer;
+    for (int i = 0; i < animatedTileCount; i++) {
+        if (animatedTiles[i] == v_layer) {
+            layer = animatedFrames[i];
+        }
+    }
+    light = v_light;
+    ambientOcclusion = v_ambient_occlusion;
 }
\ No newline at end of file
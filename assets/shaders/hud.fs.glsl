@@ -0,0 +1,30 @@
+#version 450 core
+
+out vec4 outColor;
+
+in vec2 uv;
+in vec4 color;
+flat in int layer;
+flat in int source;
+
+uniform sampler2DArray atlas;
+uniform sampler2DArray font;
+
+// Where the color comes from, matching hud.rs
+const int SOURCE_COLOR = 0;
+const int SOURCE_ATLAS = 1;
+const int SOURCE_FONT = 2;
+
+void main()
+{
+  vec4 texel = vec4(1.0);
+  if (source == SOURCE_ATLAS) {
+    texel = texture(atlas, vec3(uv, layer));
+  } else if (source == SOURCE_FONT) {
+    texel = texture(font, vec3(uv, layer));
+  }
+  outColor = texel * color;
+  if (outColor.a == 0.0) {
+    discard;
+  }
+}
@@ -0,0 +1,23 @@
+#version 450 core
+
+layout (location = 0) in vec2 v_position;
+layout (location = 1) in vec2 v_uv;
+layout (location = 2) in vec4 v_color;
+layout (location = 3) in int v_layer;
+layout (location = 4) in int v_source;
+
+uniform mat4 projection;
+
+out vec2 uv;
+out vec4 color;
+flat out int layer;
+flat out int source;
+
+void main()
+{
+    gl_Position = projection * vec4(v_position, 0.0, 1.0);
+    uv = v_uv;
+    color = v_color;
+    layer = v_layer;
+    source = v_source;
+}
@@ -0,0 +1,54 @@
+use nalgebra_glm as glm;
+
+/// Keeps resolved boxes from counting as overlapping the blocks they rest against
+pub const EPSILON: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn translated(&self, offset: &glm::Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+
+    pub fn of_block(position: &glm::IVec3) -> Self {
+        let min = glm::vec3(position.x as f32, position.y as f32, position.z as f32);
+        Self {
+            min,
+            max: min + glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Positions of every block this box overlaps
+    pub fn block_positions(&self) -> Vec<glm::IVec3> {
+        let start = glm::vec3(
+            (self.min.x + EPSILON).floor() as i32,
+            (self.min.y + EPSILON).floor() as i32,
+            (self.min.z + EPSILON).floor() as i32,
+        );
+        let end = glm::vec3(
+            (self.max.x - EPSILON).floor() as i32,
+            (self.max.y - EPSILON).floor() as i32,
+            (self.max.z - EPSILON).floor() as i32,
+        );
+        let mut positions = Vec::new();
+        for x in start.x..=end.x {
+            for y in start.y..=end.y {
+                for z in start.z..=end.z {
+                    positions.push(glm::vec3(x, y, z));
+                }
+            }
+        }
+        positions
+    }
+}
@@ -1,39 +1,233 @@
 use anyhow::Result;
 use gl::types::*;
-use glutin::{
-    dpi::PhysicalPosition,
-    event::{Event, VirtualKeyCode},
-    window::Window,
-};
+use glutin::event::{Event, StartCause};
 use nalgebra_glm as glm;
+use std::{path::Path, sync::Arc};
 
 use crate::{
-    block::{Cube, World},
+    aabb::Aabb,
+    bindings::{Action, ActionMap},
+    block::{Block, Cube},
     camera::{CameraDirection, FreeCamera},
+    frustum::{CullingStats, Frustum},
+    gamepad::{GamepadPoller, GamepadSettings},
+    hud::{layout_hud, HudQuad, HudRenderer},
     input::Input,
-    system::System,
+    inventory::{GameMode, Inventory, HOTBAR_SLOTS},
+    player::{Player, PlayerInput},
+    profiler::Profiler,
+    raycast::raycast,
+    registry::BlockRegistry,
+    replay::{Frame, Recorder, Recording},
+    save::{load_inventory, load_world, new_world, save_inventory, save_world, world_exists},
+    shader::ShaderError,
+    system::{FixedTimestep, InterpolatedPosition, ManualClock, NoWindow, System, WindowControl},
+    texture_pack::{
+        available_packs, current_frames, load_pack_layers, TexturePack, TileAnimation, DEFAULT_PACK,
+    },
+    world::{block_position_of, chunk_position_of, World},
 };
 
+pub const WINDOW_TITLE: &str = "Not minecraft!";
+pub const WORLD_SEED: u64 = 0x6e6f_746d_696e_6563;
+const SAVE_DIRECTORY: &str = "saves/world";
+const PROFILE_PATH: &str = "profile.csv";
+const REACH_DISTANCE: f32 = 8.0;
+/// Seconds between repeated breaks or placements while a mouse button is held
+const INTERACTION_INTERVAL: f64 = 0.25;
+/// Simulation steps per second
+const TICK_RATE: f64 = 60.0;
+/// Keeps a long frame from running a burst of steps that makes the next frame long too
+const MAX_TICKS_PER_FRAME: u32 = 10;
+/// The blocks on the hotbar in a new world
+const STARTING_BLOCKS: [&str; HOTBAR_SLOTS] = [
+    "dirt_with_grass",
+    "dirt",
+    "cobblestone",
+    "oak_planks",
+    "water",
+    "tnt",
+    "rose",
+    "lava",
+    "jack_o_lantern",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementMode {
+    Fly,
+    Walk,
+}
+
+pub fn load_registry() -> Result<Arc<BlockRegistry>> {
+    let tiles = TexturePack::load(DEFAULT_PACK)?;
+    Ok(Arc::new(BlockRegistry::load("assets/blocks.ron", &tiles)?))
+}
+
+/// Loads the saved world if there is one, otherwise creates a new world
+pub fn create_world() -> Result<World> {
+    let registry = load_registry()?;
+    let save_directory = Path::new(SAVE_DIRECTORY);
+    if world_exists(save_directory) {
+        load_world(save_directory, registry)
+    } else {
+        new_world(save_directory, WORLD_SEED, registry)
+    }
+}
+
+pub fn starting_inventory(registry: &BlockRegistry) -> Inventory {
+    let blocks = STARTING_BLOCKS
+        .iter()
+        .filter_map(|name| registry.block_named(name))
+        .collect::<Vec<_>>();
+    Inventory::with_blocks(GameMode::Creative, &blocks)
+}
+
 pub struct App {
     world: World,
-    block: Cube,
+    /// Missing when running without a window
+    block: Option<Cube>,
+    hud: Option<HudRenderer>,
+    view_projection: glm::Mat4,
     camera: FreeCamera,
+    player: Player,
+    movement_mode: MovementMode,
+    timestep: FixedTimestep,
+    /// The eye position at the last two ticks, which the camera is placed between
+    eye: InterpolatedPosition,
+    bindings: ActionMap,
+    gamepads: Option<GamepadPoller>,
+    gamepad_settings: GamepadSettings,
+    inventory: Inventory,
+    interaction_cooldown: f64,
+    culling_stats: CullingStats,
+    reported_culling_stats: Option<CullingStats>,
+    profiler: Profiler,
+    show_debug_overlay: bool,
+    /// The path of the texture pack in use
+    texture_pack: String,
+    /// Animations of the default pack, whose layout every pack is arranged in
+    tile_animations: Vec<TileAnimation>,
+    recorder: Option<Recorder>,
+    /// Drives the frame times when replaying
+    replay_clock: Option<ManualClock>,
+    /// Waits for requested chunks every update so recordings replay the same way
+    synchronous_loading: bool,
     pub system: System,
     pub input: Input,
 }
 
 impl App {
-    pub fn new(dimensions: [u32; 2]) -> Result<Self> {
+    pub fn new(dimensions: [u32; 2], texture_pack: &str) -> Result<Self> {
         // Self::enable_wireframe();
+        let world = create_world()?;
+        let inventory = load_inventory(Path::new(SAVE_DIRECTORY), &world.registry)?;
+        let mut app = Self::with_world(dimensions, world, Some(texture_pack))?;
+        if let Some(inventory) = inventory {
+            app.inventory = inventory;
+        }
+        Ok(app)
+    }
+
+    /// Starts from a newly generated world and records the session,
+    /// which is written out in place of saving the world
+    pub fn recording(dimensions: [u32; 2], path: &str, texture_pack: &str) -> Result<Self> {
+        let world = World::new(WORLD_SEED, load_registry()?);
+        let mut app = Self::with_world(dimensions, world, Some(texture_pack))?;
+        app.recorder = Some(Recorder::new(path, WORLD_SEED, dimensions));
+        // Flying ignores the terrain, so there is no need to stream much of it
+        app.world.view_distance = 1;
+        app.synchronous_loading = true;
+        Ok(app)
+    }
+
+    /// Creates an app without a window or renderer to replay a recording into
+    pub fn replaying(recording: &Recording) -> Result<Self> {
+        let world = World::new(recording.seed, load_registry()?);
+        let mut app = Self::with_world(recording.window_dimensions, world, None)?;
+        let clock = ManualClock::default();
+        app.system = System::with_clock(recording.window_dimensions, Box::new(clock.clone()));
+        app.replay_clock = Some(clock);
+        app.synchronous_loading = true;
+        Ok(app)
+    }
+
+    /// Creates the app around a world. Given a texture pack to draw with,
+    /// it also creates the renderers and gamepad support for a window.
+    fn with_world(
+        dimensions: [u32; 2],
+        mut world: World,
+        texture_pack: Option<&str>,
+    ) -> Result<Self> {
+        let (block, hud, gamepads) = match texture_pack {
+            Some(texture_pack) => (
+                Some(Cube::new(&load_pack_layers(texture_pack)?)?),
+                Some(HudRenderer::new()?),
+                Some(GamepadPoller::new()),
+            ),
+            None => (None, None, None),
+        };
+        let camera = FreeCamera::default();
+        world.update(&camera.position());
+        world.finish_loading();
+        let inventory = starting_inventory(&world.registry);
         Ok(Self {
-            world: World::new(),
-            block: Cube::new()?,
-            camera: FreeCamera::default(),
+            world,
+            block,
+            hud,
+            view_projection: glm::Mat4::identity(),
+            player: Player::new(camera.position()),
+            eye: InterpolatedPosition::new(camera.position()),
+            camera,
+            movement_mode: MovementMode::Fly,
+            timestep: FixedTimestep::new(TICK_RATE, MAX_TICKS_PER_FRAME),
+            bindings: ActionMap::load("assets/bindings.ron")?,
+            gamepads,
+            gamepad_settings: GamepadSettings::load("assets/gamepad.ron")?,
+            inventory,
+            interaction_cooldown: 0.0,
+            culling_stats: CullingStats::default(),
+            reported_culling_stats: None,
+            profiler: Profiler::new(),
+            show_debug_overlay: false,
+            texture_pack: texture_pack.unwrap_or(DEFAULT_PACK).to_string(),
+            tile_animations: TexturePack::load(DEFAULT_PACK)?.animations().to_vec(),
+            recorder: None,
+            replay_clock: None,
+            synchronous_loading: false,
             system: System::new(dimensions),
             input: Input::default(),
         })
     }
 
+    pub fn save(&self) -> Result<()> {
+        match self.recorder.as_ref() {
+            Some(recorder) => recorder.save(),
+            None => {
+                let directory = Path::new(SAVE_DIRECTORY);
+                save_world(&self.world, directory)?;
+                save_inventory(&self.inventory, &self.world.registry, directory)
+            }
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn camera(&self) -> &FreeCamera {
+        &self.camera
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    /// Animated tiles paired with the layers they show at the current tick
+    pub fn animated_tiles(&self) -> Vec<(i32, i32)> {
+        let time = self.world.ticks() as f64 * self.timestep.step;
+        current_frames(&self.tile_animations, time)
+    }
+
     #[allow(dead_code)]
     pub fn enable_wireframe() {
         unsafe {
@@ -41,31 +235,171 @@ impl App {
         }
     }
 
-    pub fn update(&mut self, window: &Window) -> Result<()> {
-        if self.input.is_key_pressed(VirtualKeyCode::Escape) {
+    pub fn update(&mut self, window: &dyn WindowControl) -> Result<()> {
+        if let Some(gamepads) = self.gamepads.as_mut() {
+            gamepads.poll(&mut self.input.gamepad, &self.gamepad_settings);
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.finish_frame(self.system.delta_time, &self.input.gamepad);
+        }
+        self.profiler
+            .finish_frame(self.system.delta_time, self.world.chunks.len());
+        if self.is_active(Action::Exit) {
             self.system.exit_requested = true;
         }
+        self.update_debug_overlay()?;
+        self.update_texture_pack()?;
 
-        self.update_free_camera(window)?;
+        self.update_movement_mode();
+        self.update_hotbar();
+        self.update_camera_look(window)?;
+        let ticks = self.timestep.advance(self.system.delta_time);
+        for _ in 0..ticks {
+            self.tick();
+        }
+        self.camera
+            .set_position(self.eye.at(self.timestep.alpha() as f32));
+        self.world.update(&self.camera.position());
+        if self.synchronous_loading {
+            self.world.finish_loading();
+        }
+        self.update_block_interaction();
 
-        let perspective = glm::perspective_zo(
-            self.system.aspect_ratio(),
-            80_f32.to_radians(),
-            0.01,
-            1000.0,
-        );
+        let perspective = self.camera.projection_matrix(self.system.aspect_ratio());
         let model = glm::Mat4::identity();
-        self.block.mvp = perspective * self.camera.view_matrix() * model;
+        self.view_projection = perspective * self.camera.view_matrix() * model;
+
+        self.report_culling_stats(window);
         Ok(())
     }
 
+    /// Advances the simulation by one fixed step
+    fn tick(&mut self) {
+        self.world.tick();
+        let step = self.timestep.step as f32;
+        self.camera.set_position(self.eye.current);
+        match self.movement_mode {
+            MovementMode::Fly => self.update_free_camera(step),
+            MovementMode::Walk => self.update_player(step),
+        }
+        self.eye.push(self.camera.position());
+    }
+
+    /// Replays one recorded frame and updates the app with it
+    pub fn replay_frame(&mut self, frame: &Frame) -> Result<()> {
+        if let Some(clock) = self.replay_clock.as_ref() {
+            clock.advance(frame.delta_time);
+        }
+        self.handle_events(&Event::NewEvents(StartCause::Poll))?;
+        for event in frame.events.iter() {
+            self.handle_events(&event.to_event())?;
+        }
+        frame.gamepad.restore(&mut self.input.gamepad);
+        self.update(&NoWindow)
+    }
+
     pub fn handle_events(&mut self, event: &Event<()>) -> Result<()> {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record_event(event);
+        }
         self.system.handle_event(event);
         self.input.handle_event(event, self.system.window_center());
         Ok(())
     }
 
+    /// The HUD for the current state, laid out for the window
+    pub fn hud_layout(&self) -> Vec<HudQuad> {
+        layout_hud(
+            self.system.window_dimensions,
+            &self.inventory,
+            &self.world.registry,
+            &self.debug_lines(),
+        )
+    }
+
+    fn update_debug_overlay(&mut self) -> Result<()> {
+        if self.was_just_activated(Action::ToggleDebugOverlay) {
+            self.show_debug_overlay = !self.show_debug_overlay;
+        }
+        if self.was_just_activated(Action::DumpProfile) {
+            self.profiler.save_csv(PROFILE_PATH)?;
+            println!("Wrote frame statistics to '{}'", PROFILE_PATH);
+        }
+        Ok(())
+    }
+
+    /// Switches to the next texture pack, looking for new packs each time.
+    /// A pack that fails to load is reported and the current one is kept.
+    fn update_texture_pack(&mut self) -> Result<()> {
+        if !self.was_just_activated(Action::NextTexturePack) {
+            return Ok(());
+        }
+        let packs = available_packs();
+        let current = &self.texture_pack;
+        let next = match packs.iter().position(|pack| pack == current) {
+            Some(index) => (index + 1) % packs.len(),
+            None => 0,
+        };
+        let block = match self.block.as_mut() {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        match load_pack_layers(&packs[next]) {
+            Ok(tiles) => {
+                block.set_tiles(&tiles)?;
+                println!("Using texture pack '{}'", packs[next]);
+            }
+            Err(error) => eprintln!("{:#}", error),
+        }
+        // Skip a broken pack next time rather than retrying it
+        self.texture_pack = packs[next].clone();
+        Ok(())
+    }
+
+    /// Debug text and frame statistics, shown while the debug overlay is enabled
+    fn debug_lines(&self) -> Vec<String> {
+        if !self.show_debug_overlay {
+            return Vec::new();
+        }
+        let position = self.camera.position();
+        let chunk = chunk_position_of(&block_position_of(&position));
+        vec![
+            format!("{:.0} fps", self.system.frame_rate),
+            format!(
+                "Position {:.1} {:.1} {:.1}",
+                position.x, position.y, position.z
+            ),
+            format!("Chunk {} {}", chunk.x, chunk.y),
+            format!(
+                "{} chunks drawn, {} culled",
+                self.culling_stats.drawn, self.culling_stats.culled
+            ),
+            format!(
+                "{:?} mode, {:?}",
+                self.movement_mode, self.inventory.game_mode
+            ),
+        ]
+        .into_iter()
+        .chain(self.profiler.summary())
+        .collect()
+    }
+
     pub fn render(&mut self) -> Result<()> {
+        let hud_quads = self.hud_layout();
+        let animated_tiles = self.animated_tiles();
+        let block = match self.block.as_mut() {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        report_shader_reload("block", block.reload_shaders());
+        if let Some(hud) = self.hud.as_mut() {
+            report_shader_reload("HUD", hud.reload_shaders());
+        }
+        let dirty_chunks = self.world.take_dirty_chunks();
+        block.update_chunk_meshes(&self.world, &dirty_chunks, &mut self.profiler);
+        block.mvp = self.view_projection;
+        block.animated_tiles = animated_tiles;
+
         unsafe {
             gl::Enable(gl::DEPTH_TEST);
 
@@ -73,39 +407,289 @@ impl App {
             gl::ClearBufferfv(gl::COLOR, 0, background_color as *const f32);
             gl::ClearBufferfv(gl::DEPTH, 0, &[1.0 as GLfloat] as *const f32);
 
-            self.block.draw_world(&self.world)?;
+            let frustum = Frustum::from_view_projection(&self.view_projection);
+            self.culling_stats = block.draw_world(
+                &self.world,
+                &frustum,
+                &self.camera.position(),
+                &mut self.profiler,
+            )?;
+            if let Some(hud) = self.hud.as_ref() {
+                hud.draw(
+                    &hud_quads,
+                    block.atlas(),
+                    self.system.window_dimensions,
+                    &mut self.profiler,
+                )?;
+            }
         }
         Ok(())
     }
 
-    fn update_free_camera(&mut self, window: &Window) -> Result<()> {
-        let delta_time = self.system.delta_time as f32;
-        if self.input.is_key_pressed(VirtualKeyCode::W) {
-            self.camera.translate(CameraDirection::Forward, delta_time);
+    /// Shows how many chunks were drawn in the last frame in the window title
+    fn report_culling_stats(&mut self, window: &dyn WindowControl) {
+        if self.reported_culling_stats == Some(self.culling_stats) {
+            return;
+        }
+        window.set_title(&format!(
+            "{} - {} chunks drawn, {} culled",
+            WINDOW_TITLE, self.culling_stats.drawn, self.culling_stats.culled
+        ));
+        self.reported_culling_stats = Some(self.culling_stats);
+    }
+
+    fn update_block_interaction(&mut self) {
+        self.interaction_cooldown -= self.system.delta_time;
+
+        // Releasing a button lets the next press act immediately
+        if self
+            .bindings
+            .was_just_deactivated(&self.input, Action::BreakBlock)
+            || self
+                .bindings
+                .was_just_deactivated(&self.input, Action::PlaceBlock)
+        {
+            self.interaction_cooldown = 0.0;
+        }
+
+        let (breaking, placing) = (
+            self.is_active(Action::BreakBlock),
+            self.is_active(Action::PlaceBlock),
+        );
+        if !breaking && !placing {
+            return;
+        }
+        if self.interaction_cooldown > 0.0 {
+            return;
+        }
+
+        let hit = match raycast(
+            &self.world,
+            &self.camera.position(),
+            &self.camera.front(),
+            REACH_DISTANCE,
+        ) {
+            Some(hit) => hit,
+            None => return,
+        };
+
+        if breaking {
+            if hit.block != Block::Bedrock {
+                self.world.set_block(&hit.position, Block::Air);
+                // Blocks that don't fit in a full inventory are lost
+                self.inventory.collect(hit.block);
+            }
+        } else {
+            let target = hit.position + hit.normal;
+            let obstructed = self.movement_mode == MovementMode::Walk
+                && self.player.aabb().intersects(&Aabb::of_block(&target));
+            let replaceable = match self.world.block_at(&target) {
+                Some(block) => block == Block::Air || block.fluid().is_some(),
+                None => false,
+            };
+            if !obstructed && replaceable {
+                if let Some(block) = self.inventory.take_selected() {
+                    self.world.set_block(&target, block);
+                }
+            }
+        }
+        self.interaction_cooldown = INTERACTION_INTERVAL;
+    }
+
+    fn update_movement_mode(&mut self) {
+        if self
+            .bindings
+            .was_just_activated(&self.input, Action::ToggleMovementMode)
+        {
+            self.movement_mode = match self.movement_mode {
+                MovementMode::Fly => {
+                    self.player = Player::new(self.eye.current - self.player_eye_offset());
+                    MovementMode::Walk
+                }
+                MovementMode::Walk => MovementMode::Fly,
+            };
+        }
+    }
+
+    fn update_hotbar(&mut self) {
+        for slot in 0..HOTBAR_SLOTS {
+            if self.was_just_activated(Action::SelectSlot(slot)) {
+                self.inventory.select(slot);
+            }
         }
-        if self.input.is_key_pressed(VirtualKeyCode::A) {
-            self.camera.translate(CameraDirection::Left, delta_time);
+        if self.was_just_activated(Action::NextSlot) {
+            self.inventory.scroll(1);
         }
-        if self.input.is_key_pressed(VirtualKeyCode::S) {
-            self.camera.translate(CameraDirection::Backward, delta_time);
+        if self.was_just_activated(Action::PreviousSlot) {
+            self.inventory.scroll(-1);
         }
-        if self.input.is_key_pressed(VirtualKeyCode::D) {
-            self.camera.translate(CameraDirection::Right, delta_time);
+        // Scrolling down moves toward the end of the hotbar
+        let wheel = self.input.mouse.wheel_delta.y;
+        if wheel != 0.0 {
+            self.inventory.scroll(-wheel.signum() as i32);
         }
-        if self.input.is_key_pressed(VirtualKeyCode::LShift) {
+
+        if self.was_just_activated(Action::ToggleGameMode) {
+            self.inventory.game_mode = match self.inventory.game_mode {
+                GameMode::Creative => GameMode::Survival,
+                GameMode::Survival => GameMode::Creative,
+            };
+        }
+    }
+
+    fn was_just_activated(&self, action: Action) -> bool {
+        self.bindings.was_just_activated(&self.input, action)
+    }
+
+    fn is_active(&self, action: Action) -> bool {
+        self.bindings.is_active(&self.input, action)
+    }
+
+    fn player_eye_offset(&self) -> glm::Vec3 {
+        self.player.eye_position() - self.player.position
+    }
+
+    /// Forward and rightward movement from the keyboard and gamepad, each in [-1, 1]
+    fn movement_input(&self) -> glm::Vec2 {
+        let mut movement = self.gamepad_settings.movement(&self.input.gamepad);
+        if self.is_active(Action::MoveForward) {
+            movement.x += 1.0;
+        }
+        if self.is_active(Action::MoveBackward) {
+            movement.x -= 1.0;
+        }
+        if self.is_active(Action::MoveRight) {
+            movement.y += 1.0;
+        }
+        if self.is_active(Action::MoveLeft) {
+            movement.y -= 1.0;
+        }
+        glm::vec2(movement.x.clamp(-1.0, 1.0), movement.y.clamp(-1.0, 1.0))
+    }
+
+    fn update_player(&mut self, delta_time: f32) {
+        let input = PlayerInput {
+            movement: self.movement_input(),
+            facing: self.camera.front(),
+            jump: self.is_active(Action::Jump),
+            sneak: self.is_active(Action::Sneak),
+        };
+        self.player.step(&self.world, &input, delta_time);
+        self.camera.set_position(self.player.eye_position());
+    }
+
+    fn update_free_camera(&mut self, delta_time: f32) {
+        // Partial stick deflection moves the camera for part of the frame's time
+        let movement = self.movement_input();
+        if movement.x > 0.0 {
+            self.camera
+                .translate(CameraDirection::Forward, delta_time * movement.x);
+        }
+        if movement.x < 0.0 {
+            self.camera
+                .translate(CameraDirection::Backward, delta_time * -movement.x);
+        }
+        if movement.y > 0.0 {
+            self.camera
+                .translate(CameraDirection::Right, delta_time * movement.y);
+        }
+        if movement.y < 0.0 {
+            self.camera
+                .translate(CameraDirection::Left, delta_time * -movement.y);
+        }
+        if self.is_active(Action::Sneak) {
             self.camera.translate(CameraDirection::Down, delta_time);
         }
-        if self.input.is_key_pressed(VirtualKeyCode::Space) {
+        if self.is_active(Action::Jump) {
             self.camera.translate(CameraDirection::Up, delta_time);
         }
-        let offset = self.input.mouse.offset_from_center;
+    }
+
+    fn update_camera_look(&mut self, window: &dyn WindowControl) -> Result<()> {
+        let offset = self.input.mouse.offset_from_center
+            + self
+                .gamepad_settings
+                .look_offset(&self.input.gamepad, self.system.delta_time as f32);
         self.camera.process_mouse_movement(offset.x, offset.y);
 
-        window.set_cursor_grab(true)?;
-        window.set_cursor_visible(false);
-        let center = self.system.window_center();
-        window.set_cursor_position(PhysicalPosition::new(center.x, center.y))?;
+        window.grab_cursor(self.system.window_center())
+    }
+}
 
-        Ok(())
+/// Tells shader authors whether their edits took effect
+fn report_shader_reload(name: &str, result: Result<bool, ShaderError>) {
+    match result {
+        Ok(true) => println!("Reloaded the {} shaders", name),
+        Ok(false) => {}
+        Err(error) => eprintln!("{}\nKeeping the previous {} shaders", error, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::{GamepadSnapshot, InputEvent};
+    use glutin::event::VirtualKeyCode;
+
+    const FRAME_TIME: f64 = 1.0 / 60.0;
+
+    fn frame(events: Vec<InputEvent>) -> Frame {
+        Frame {
+            delta_time: FRAME_TIME,
+            events,
+            gamepad: GamepadSnapshot::default(),
+        }
+    }
+
+    fn key(key: VirtualKeyCode, pressed: bool) -> InputEvent {
+        InputEvent::Key { key, pressed }
+    }
+
+    /// Walks forward while turning, then strafes with the gamepad stick
+    fn session(center: glm::Vec2) -> Vec<Frame> {
+        let mut frames = vec![frame(vec![key(VirtualKeyCode::W, true)])];
+        for step in 0..30 {
+            frames.push(frame(vec![InputEvent::CursorMoved {
+                x: (center.x + step as f32) as f64,
+                y: center.y as f64,
+            }]));
+        }
+        frames.push(frame(vec![key(VirtualKeyCode::W, false)]));
+        for _ in 0..20 {
+            let mut frame = frame(Vec::new());
+            frame.gamepad.left_stick = [1.0, 0.0];
+            frames.push(frame);
+        }
+        frames.push(frame(Vec::new()));
+        frames
+    }
+
+    #[test]
+    fn replaying_a_recording_ends_at_the_same_position() {
+        let path =
+            std::env::temp_dir().join(format!("notminecraft-recording-{}.ron", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let dimensions = [640, 480];
+        let mut app = App::replaying(&Recording::new(WORLD_SEED, dimensions)).unwrap();
+        app.recorder = Some(Recorder::new(path, WORLD_SEED, dimensions));
+        // Flying ignores the terrain, so there is no need to stream much of it
+        app.world.view_distance = 1;
+        for frame in session(app.system.window_center()) {
+            app.replay_frame(&frame).unwrap();
+        }
+        app.save().unwrap();
+        let start = FreeCamera::default().position();
+        assert!(glm::distance(&app.camera().position(), &start) > 1.0);
+
+        let recording = Recording::load(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        let mut replay = App::replaying(&recording).unwrap();
+        replay.world.view_distance = 1;
+        for frame in recording.frames.iter() {
+            replay.replay_frame(frame).unwrap();
+        }
+        assert_eq!(replay.camera().position(), app.camera().position());
+        assert_eq!(replay.camera().front(), app.camera().front());
     }
 }
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use glutin::event::{MouseButton, VirtualKeyCode};
+use serde::Deserialize;
+use std::{collections::HashMap, fs};
+
+use crate::{gamepad::GamepadButton, input::Input};
+
+/// Something the player can do, independent of the inputs bound to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Also flies upward in fly mode
+    Jump,
+    /// Also flies downward in fly mode
+    Sneak,
+    BreakBlock,
+    PlaceBlock,
+    /// Selects a hotbar slot, counting from 0
+    SelectSlot(usize),
+    NextSlot,
+    PreviousSlot,
+    ToggleMovementMode,
+    /// Switches between creative and survival mode
+    ToggleGameMode,
+    /// Shows or hides the debug text and frame statistics
+    ToggleDebugOverlay,
+    /// Writes the recent frame statistics to a CSV file
+    DumpProfile,
+    /// Switches to the next texture pack in texture_packs/
+    NextTexturePack,
+    Exit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+/// Modifier keys, each matching either its left or right key
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Alt,
+}
+
+impl Modifier {
+    fn is_held(&self, input: &Input) -> bool {
+        let (left, right) = match *self {
+            Modifier::Shift => (VirtualKeyCode::LShift, VirtualKeyCode::RShift),
+            Modifier::Control => (VirtualKeyCode::LControl, VirtualKeyCode::RControl),
+            Modifier::Alt => (VirtualKeyCode::LAlt, VirtualKeyCode::RAlt),
+        };
+        input.is_key_pressed(left) || input.is_key_pressed(right)
+    }
+}
+
+/// A key or mouse button that performs an action while the listed modifiers are held.
+/// Bindings without modifiers still work while modifiers are held.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Binding {
+    pub trigger: Trigger,
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Binding {
+    fn modifiers_held(&self, input: &Input) -> bool {
+        self.modifiers
+            .iter()
+            .all(|modifier| modifier.is_held(input))
+    }
+
+    fn is_active(&self, input: &Input) -> bool {
+        let pressed = match self.trigger {
+            Trigger::Key(keycode) => input.is_key_pressed(keycode),
+            Trigger::Mouse(button) => input.mouse.is_button_pressed(button),
+            Trigger::Gamepad(button) => input.gamepad.is_button_pressed(button),
+        };
+        pressed && self.modifiers_held(input)
+    }
+
+    fn was_just_activated(&self, input: &Input) -> bool {
+        let pressed = match self.trigger {
+            Trigger::Key(keycode) => input.is_key_just_pressed(keycode),
+            Trigger::Mouse(button) => input.mouse.is_button_just_pressed(button),
+            Trigger::Gamepad(button) => input.gamepad.is_button_just_pressed(button),
+        };
+        pressed && self.modifiers_held(input)
+    }
+
+    fn was_just_deactivated(&self, input: &Input) -> bool {
+        match self.trigger {
+            Trigger::Key(keycode) => input.is_key_just_released(keycode),
+            Trigger::Mouse(button) => input.mouse.is_button_just_released(button),
+            Trigger::Gamepad(button) => input.gamepad.is_button_just_released(button),
+        }
+    }
+}
+
+/// Maps actions to the inputs that perform them.
+/// An action can have any number of bindings and is active when any of them is.
+pub struct ActionMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl ActionMap {
+    /// Loads bindings from a RON file mapping each action to a list of bindings
+    pub fn load(path: &str) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read key bindings '{}'", path))?;
+        let bindings = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse key bindings '{}'", path))?;
+        Ok(Self { bindings })
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether the action is currently held
+    pub fn is_active(&self, input: &Input, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.is_active(input))
+    }
+
+    /// Whether one of the action's bindings was pressed since the last batch of events
+    pub fn was_just_activated(&self, input: &Input, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.was_just_activated(input))
+    }
+
+    /// Whether one of the action's bindings was released since the last batch of events
+    pub fn was_just_deactivated(&self, input: &Input, action: Action) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.was_just_deactivated(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::InputEvent;
+    use glutin::event::{Event, StartCause};
+    use nalgebra_glm as glm;
+    use std::path::PathBuf;
+
+    fn action_map(source: &str) -> ActionMap {
+        ActionMap {
+            bindings: ron::from_str(source).unwrap(),
+        }
+    }
+
+    fn send(input: &mut Input, event: InputEvent) {
+        input.handle_event(&event.to_event(), glm::Vec2::zeros());
+    }
+
+    fn new_events(input: &mut Input) {
+        input.handle_event(
+            &Event::<()>::NewEvents(StartCause::Poll),
+            glm::Vec2::zeros(),
+        );
+    }
+
+    fn scratch_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("notminecraft-{}-{}.ron", name, std::process::id()))
+    }
+
+    #[test]
+    fn modifiers_must_be_held() {
+        let actions = action_map("{ Exit: [(trigger: Key(Q), modifiers: [Control])] }");
+        let mut input = Input::default();
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::Q,
+                pressed: true,
+            },
+        );
+        assert!(!actions.is_active(&input, Action::Exit));
+        assert!(!actions.was_just_activated(&input, Action::Exit));
+
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::Q,
+                pressed: false,
+            },
+        );
+        new_events(&mut input);
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::RControl,
+                pressed: true,
+            },
+        );
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::Q,
+                pressed: true,
+            },
+        );
+        assert!(actions.is_active(&input, Action::Exit));
+        assert!(actions.was_just_activated(&input, Action::Exit));
+    }
+
+    #[test]
+    fn any_binding_activates_the_action() {
+        let actions =
+            action_map("{ BreakBlock: [(trigger: Key(X)), (trigger: Mouse(Left))], Jump: [] }");
+        let mut input = Input::default();
+        assert!(!actions.is_active(&input, Action::BreakBlock));
+
+        send(
+            &mut input,
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                pressed: true,
+            },
+        );
+        assert!(actions.is_active(&input, Action::BreakBlock));
+        assert!(actions.was_just_activated(&input, Action::BreakBlock));
+        send(
+            &mut input,
+            InputEvent::MouseButton {
+                button: MouseButton::Left,
+                pressed: false,
+            },
+        );
+        assert!(actions.was_just_deactivated(&input, Action::BreakBlock));
+
+        new_events(&mut input);
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::X,
+                pressed: true,
+            },
+        );
+        assert!(actions.is_active(&input, Action::BreakBlock));
+        assert!(actions.was_just_activated(&input, Action::BreakBlock));
+        assert!(!actions.is_active(&input, Action::Jump));
+        assert!(!actions.is_active(&input, Action::Exit));
+    }
+
+    #[test]
+    fn held_bindings_do_not_activate_again() {
+        let actions = action_map("{ Jump: [(trigger: Key(Space))] }");
+        let mut input = Input::default();
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::Space,
+                pressed: true,
+            },
+        );
+        assert!(actions.was_just_activated(&input, Action::Jump));
+        new_events(&mut input);
+        send(
+            &mut input,
+            InputEvent::Key {
+                key: VirtualKeyCode::Space,
+                pressed: true,
+            },
+        );
+        assert!(actions.is_active(&input, Action::Jump));
+        assert!(!actions.was_just_activated(&input, Action::Jump));
+    }
+
+    #[test]
+    fn loads_the_default_bindings() {
+        let actions = ActionMap::load("assets/bindings.ron").unwrap();
+        assert_eq!(actions.bindings(Action::Exit).len(), 2);
+    }
+
+    #[test]
+    fn malformed_bindings_are_an_error() {
+        let path = scratch_file("malformed-bindings");
+        fs::write(&path, "{ Exit: [(trigger: Key(NotAKey))] }").unwrap();
+        let result = ActionMap::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let error = format!("{:#}", result.err().expect("Expected an error"));
+        assert!(error.contains("Failed to parse key bindings"), "{}", error);
+
+        let error = ActionMap::load("assets/missing-bindings.ron")
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("Failed to read key bindings"));
+    }
+}
@@ -1,51 +1,54 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use gl::types::*;
-use image::{EncodableLayout, GenericImageView};
+use image::{EncodableLayout, RgbaImage};
 use nalgebra_glm as glm;
-use std::{ffi::CString, fs};
-
-const CHUNK_WIDTH: usize = 4;
-const CHUNK_LENGTH: usize = 4;
-const CHUNK_DEPTH: usize = 8;
-const WORLD_WIDTH: usize = 4;
-const WORLD_LENGTH: usize = 4;
-
-pub struct World {
-    pub chunks: Vec<Vec<Chunk>>,
-}
-
-impl World {
-    pub fn new() -> Self {
-        let mut chunks = Vec::new();
-        for y in 0..WORLD_LENGTH {
-            let mut chunks_x = Vec::new();
-            for x in 0..WORLD_WIDTH {
-                let mut chunk = Chunk::default();
-                chunk.position = glm::vec3((x * CHUNK_WIDTH) as f32, (y * CHUNK_LENGTH) as _, 0.0);
-                chunks_x.push(chunk);
-            }
-            chunks.push(chunks_x);
-        }
-        Self { chunks }
-    }
-}
-
-pub struct Chunk {
-    pub position: glm::Vec3,
-    pub blocks: [[[Block; CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH],
-}
-
-impl Default for Chunk {
-    fn default() -> Self {
-        let blocks = [[[Block::default(); CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH];
-        Self {
-            position: glm::vec3(0.0, 0.0, 0.0),
-            blocks,
-        }
-    }
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
+
+use crate::{
+    fluid::Fluid,
+    frustum::{CullingStats, Frustum},
+    mesh::{mesh_chunk, GpuMesh, Mesh},
+    profiler::Profiler,
+    registry::Faces,
+    shader::{ShaderError, ShaderProgram},
+    texture_pack::{TexturePack, MAX_ANIMATED_TILES},
+    world::{block_position_of, ChunkPosition, World},
+};
+
+/// The tile index of faces that aren't drawn
+pub const NO_TILE: i32 = -1;
+
+/// Names of the water blocks by level, starting with the source
+const WATER_NAMES: [&str; 8] = [
+    "water",
+    "flowing_water_1",
+    "flowing_water_2",
+    "flowing_water_3",
+    "flowing_water_4",
+    "flowing_water_5",
+    "flowing_water_6",
+    "flowing_water_7",
+];
+/// Names of the lava blocks by level, starting with the source
+const LAVA_NAMES: [&str; 4] = ["lava", "flowing_lava_1", "flowing_lava_2", "flowing_lava_3"];
+
+/// How a block's faces are blended with what is behind them
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum RenderLayer {
+    /// Fully covers what is behind it
+    #[default]
+    Opaque,
+    /// Either fully covers or fully shows what is behind it, pixel by pixel
+    Cutout,
+    /// Partially shows what is behind it, drawn after everything else from back to front
+    Translucent,
 }
 
-#[derive(Default)]
+#[derive(Default, Debug, Clone)]
 pub struct BlockConfiguration {
     pub left: i32,
     pub right: i32,
@@ -55,37 +58,43 @@ pub struct BlockConfiguration {
     pub bottom: i32,
     pub is_entity: bool,
     pub is_solid: bool,
+    /// Block light level given off by the block, from 0 to 15
+    pub light_emission: u8,
+    pub render_layer: RenderLayer,
 }
 
 impl BlockConfiguration {
     pub fn empty() -> Self {
         Self {
-            left: Tile::Air as _,
-            right: Tile::Air as _,
-            front: Tile::Air as _,
-            back: Tile::Air as _,
-            top: Tile::Air as _,
-            bottom: Tile::Air as _,
+            left: NO_TILE,
+            right: NO_TILE,
+            front: NO_TILE,
+            back: NO_TILE,
+            top: NO_TILE,
+            bottom: NO_TILE,
             is_entity: false,
             is_solid: false,
+            light_emission: 0,
+            render_layer: RenderLayer::Opaque,
         }
     }
 
-    pub fn new(left: Tile, right: Tile, front: Tile, back: Tile, top: Tile, bottom: Tile) -> Self {
+    pub fn new(left: i32, right: i32, front: i32, back: i32, top: i32, bottom: i32) -> Self {
         Self {
-            left: left as _,
-            right: right as _,
-            front: front as _,
-            back: back as _,
-            top: top as _,
-            bottom: bottom as _,
+            left,
+            right,
+            front,
+            back,
+            top,
+            bottom,
             is_entity: false,
             is_solid: true,
+            light_emission: 0,
+            render_layer: RenderLayer::Opaque,
         }
     }
 
-    pub fn new_single(id: Tile) -> Self {
-        let id = id as i32;
+    pub fn new_single(id: i32) -> Self {
         Self {
             left: id,
             right: id,
@@ -95,54 +104,62 @@ impl BlockConfiguration {
             bottom: id,
             is_entity: false,
             is_solid: true,
+            light_emission: 0,
+            render_layer: RenderLayer::Opaque,
         }
     }
 
-    pub fn new_same_sides(sides: Tile, top: Tile, bottom: Tile) -> Self {
-        let sides = sides as i32;
+    pub fn new_same_sides(sides: i32, top: i32, bottom: i32) -> Self {
         Self {
             left: sides,
             right: sides,
             front: sides,
             back: sides,
-            top: top as _,
-            bottom: bottom as _,
+            top,
+            bottom,
             is_entity: false,
             is_solid: true,
+            light_emission: 0,
+            render_layer: RenderLayer::Opaque,
         }
     }
 
-    pub fn new_entity(tile: Tile) -> Self {
-        let mut config = Self::default();
-        config.front = tile as _;
-        config.is_entity = true;
-        config.is_solid = false;
-        config
+    pub fn new_entity(tile: i32) -> Self {
+        Self {
+            front: tile,
+            is_entity: true,
+            is_solid: false,
+            render_layer: RenderLayer::Cutout,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the block is drawn as a cube, which is any non-entity block with a tile
+    pub fn is_cube(&self) -> bool {
+        let faces = [
+            self.left,
+            self.right,
+            self.front,
+            self.back,
+            self.top,
+            self.bottom,
+        ];
+        !self.is_entity && faces.iter().any(|tile| *tile != NO_TILE)
+    }
+
+    /// Whether the block hides the faces of blocks next to it and stops light
+    pub fn is_opaque(&self) -> bool {
+        self.is_solid && !self.is_entity && self.render_layer == RenderLayer::Opaque
     }
-}
-pub enum Tile {
-    Air = -1,
-    Gravel,
-    DirtSnowSide,
-    Grass,
-    DirtGrassSide,
-    Cobblestone = 26,
-    Bedrock = 32,
-    Dirt = 50,
-    OakPlanks = 53,
-    TntSide = 62,
-    TntTop,
-    TntBottom,
-    Rose = 68,
-    Thistle,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Block {
     Air,
     Gravel,
     Grass,
     DirtWithGrass,
+    #[default]
     Dirt,
     Cobblestone,
     Tnt,
@@ -150,424 +167,402 @@ pub enum Block {
     OakPlanks,
     Rose,
     Thistle,
+    /// How far the water has flowed from a source block, which is level 0
+    Water(u8),
+    /// How far the lava has flowed from a source block, which is level 0
+    Lava(u8),
+    /// A block defined only in the block registry file
+    Custom(u8),
 }
 
-impl Default for Block {
-    fn default() -> Self {
-        Self::Dirt
+impl Block {
+    pub const BUILT_IN: [Block; 11] = [
+        Block::Air,
+        Block::Gravel,
+        Block::Grass,
+        Block::DirtWithGrass,
+        Block::Dirt,
+        Block::Cobblestone,
+        Block::Tnt,
+        Block::Bedrock,
+        Block::OakPlanks,
+        Block::Rose,
+        Block::Thistle,
+    ];
+
+    /// The built-in blocks along with every level of each fluid
+    pub fn all_built_in() -> impl Iterator<Item = Block> {
+        let fluid_blocks = Fluid::ALL
+            .iter()
+            .flat_map(|fluid| (0..=fluid.max_level()).map(move |level| fluid.block(level)));
+        Block::BUILT_IN.iter().copied().chain(fluid_blocks)
     }
-}
 
-impl Block {
-    // TODO: Make this generate a dictionary instead
-    fn configuration(&self) -> BlockConfiguration {
+    /// The fluid held by the block and its level, if it is one
+    pub fn fluid(&self) -> Option<(Fluid, u8)> {
         match *self {
-            Block::Air => BlockConfiguration::default(),
-            Block::Gravel => BlockConfiguration::new_single(Tile::Gravel),
-            Block::Grass => BlockConfiguration::new_single(Tile::Grass),
-            Block::Dirt => BlockConfiguration::new_single(Tile::Dirt),
-            Block::DirtWithGrass => {
-                BlockConfiguration::new_same_sides(Tile::DirtGrassSide, Tile::Grass, Tile::Dirt)
-            }
-            Block::Cobblestone => BlockConfiguration::new_single(Tile::Cobblestone),
-            Block::Tnt => {
-                BlockConfiguration::new_same_sides(Tile::TntSide, Tile::TntTop, Tile::TntBottom)
-            }
-            Block::Bedrock => BlockConfiguration::new_single(Tile::Bedrock),
-            Block::OakPlanks => BlockConfiguration::new_single(Tile::OakPlanks),
-            Block::Rose => BlockConfiguration::new_entity(Tile::Rose),
-            Block::Thistle => BlockConfiguration::new_entity(Tile::Thistle),
-            _ => BlockConfiguration::default(),
+            Block::Water(level) => Some((Fluid::Water, level)),
+            Block::Lava(level) => Some((Fluid::Lava, level)),
+            _ => None,
+        }
+    }
+
+    /// The name used to refer to a built-in block in the block registry file
+    pub fn built_in_name(&self) -> Option<&'static str> {
+        let name = match *self {
+            Block::Air => "air",
+            Block::Gravel => "gravel",
+            Block::Grass => "grass",
+            Block::DirtWithGrass => "dirt_with_grass",
+            Block::Dirt => "dirt",
+            Block::Cobblestone => "cobblestone",
+            Block::Tnt => "tnt",
+            Block::Bedrock => "bedrock",
+            Block::OakPlanks => "oak_planks",
+            Block::Rose => "rose",
+            Block::Thistle => "thistle",
+            Block::Water(level) => return WATER_NAMES.get(level as usize).copied(),
+            Block::Lava(level) => return LAVA_NAMES.get(level as usize).copied(),
+            Block::Custom(_) => return None,
+        };
+        Some(name)
+    }
+
+    /// The faces used when the block registry file doesn't define the block
+    pub fn default_faces(&self) -> Faces {
+        let tile = |name: &str| name.to_string();
+        match *self {
+            Block::Air | Block::Custom(_) => Faces::Empty,
+            Block::Gravel => Faces::Single(tile("gravel")),
+            Block::Grass => Faces::Single(tile("grass")),
+            Block::Dirt => Faces::Single(tile("dirt")),
+            Block::DirtWithGrass => Faces::SameSides {
+                sides: tile("dirt_grass_side"),
+                top: tile("grass"),
+                bottom: tile("dirt"),
+            },
+            Block::Cobblestone => Faces::Single(tile("cobblestone")),
+            Block::Tnt => Faces::SameSides {
+                sides: tile("tnt_side"),
+                top: tile("tnt_top"),
+                bottom: tile("tnt_bottom"),
+            },
+            Block::Bedrock => Faces::Single(tile("bedrock")),
+            Block::OakPlanks => Faces::Single(tile("oak_planks")),
+            Block::Rose => Faces::Entity(tile("rose")),
+            Block::Thistle => Faces::Entity(tile("thistle")),
+            Block::Water(_) => Faces::Single(tile("water")),
+            Block::Lava(_) => Faces::Single(tile("lava")),
+        }
+    }
+
+    /// The configuration used when the block registry file doesn't define the block.
+    /// Fluids aren't solid and take their look from the fluid.
+    pub fn default_configuration(&self, tiles: &TexturePack) -> Result<BlockConfiguration> {
+        let mut configuration = self.default_faces().configuration(tiles)?;
+        if let Some((fluid, _)) = self.fluid() {
+            configuration.is_solid = false;
+            configuration.render_layer = fluid.render_layer();
+            configuration.light_emission = fluid.light_emission();
         }
+        Ok(configuration)
     }
 }
 
-#[rustfmt::skip]
-pub const VERTICES: &[f32; 180] =
-    &[
-        // back
-       -0.5, -0.5, -0.5,  0.0, 0.0,
-        0.5, -0.5, -0.5,  1.0, 0.0,
-        0.5,  0.5, -0.5,  1.0, 1.0,
-        0.5,  0.5, -0.5,  1.0, 1.0,
-       -0.5,  0.5, -0.5,  0.0, 1.0,
-       -0.5, -0.5, -0.5,  0.0, 0.0,
-
-       // front
-       -0.5, -0.5,  0.5,  0.0, 0.0,
-        0.5, -0.5,  0.5,  1.0, 0.0,
-        0.5,  0.5,  0.5,  1.0, 1.0,
-        0.5,  0.5,  0.5,  1.0, 1.0,
-       -0.5,  0.5,  0.5,  0.0, 1.0,
-       -0.5, -0.5,  0.5,  0.0, 0.0,
-
-       // left
-       -0.5,  0.5,  0.5,  1.0, 1.0, // upper right
-       -0.5,  0.5, -0.5,  0.0, 1.0, // upper left
-       -0.5, -0.5, -0.5,  0.0, 0.0, // lower left
-       -0.5, -0.5, -0.5,  0.0, 0.0, // lower left
-       -0.5, -0.5,  0.5,  1.0, 0.0, // lower right
-       -0.5,  0.5,  0.5,  1.0, 1.0, // upper right
-
-        // right
-        0.5,  0.5,  0.5,  0.0, 1.0, // upper left
-        0.5,  0.5, -0.5,  1.0, 1.0, // upper right
-        0.5, -0.5, -0.5,  1.0, 0.0, // lower right
-        0.5, -0.5, -0.5,  1.0, 0.0, // lower right
-        0.5, -0.5,  0.5,  0.0, 0.0, // lower left
-        0.5,  0.5,  0.5,  0.0, 1.0, // upper left
-
-        // bottom
-       -0.5, -0.5, -0.5,  0.0, 1.0, // upper left
-        0.5, -0.5, -0.5,  1.0, 1.0, // upper right
-        0.5, -0.5,  0.5,  1.0, 0.0, // lower right
-        0.5, -0.5,  0.5,  1.0, 0.0, // lower right
-       -0.5, -0.5,  0.5,  0.0, 0.0, // lower left
-       -0.5, -0.5, -0.5,  0.0, 1.0, // upper left
-
-        // top
-       -0.5,  0.5, -0.5,  0.0, 1.0,
-        0.5,  0.5, -0.5,  1.0, 1.0,
-        0.5,  0.5,  0.5,  1.0, 0.0,
-        0.5,  0.5,  0.5,  1.0, 0.0,
-       -0.5,  0.5,  0.5,  0.0, 0.0,
-       -0.5,  0.5, -0.5,  0.0, 1.0
-    ];
+/// A chunk's meshes on the GPU
+struct ChunkGpuMesh {
+    opaque: GpuMesh,
+    translucent: Option<TranslucentMesh>,
+}
+
+/// How far the camera can move within a block before translucent faces are sorted again
+const RESORT_DISTANCE: f32 = 0.5;
+
+/// Translucent faces are kept on the CPU too, to be sorted again as the camera moves
+struct TranslucentMesh {
+    mesh: Mesh,
+    gpu: GpuMesh,
+    /// The camera position relative to the chunk that the faces were last sorted for
+    sorted_for: Option<glm::Vec3>,
+}
 
 pub struct Cube {
-    vao: GLuint,
-    vbo: GLuint,
-    shader_program: GLuint,
+    shader: ShaderProgram,
     atlas: GLuint,
+    chunk_meshes: HashMap<ChunkPosition, ChunkGpuMesh>,
     pub mvp: glm::Mat4,
+    /// Animated tiles paired with the layers of their current frames
+    pub animated_tiles: Vec<(i32, i32)>,
 }
 
 impl Cube {
-    pub fn new() -> Result<Self> {
+    /// Creates the block renderer with the tiles of a texture pack
+    pub fn new(tiles: &[RgbaImage]) -> Result<Self> {
         Ok(Self {
-            vao: Self::create_vao(),
-            vbo: Self::create_vbo(),
-            shader_program: Self::create_shader_program()?,
-            atlas: Self::create_atlas()?,
+            shader: ShaderProgram::new(
+                "assets/shaders/block.vs.glsl",
+                "assets/shaders/block.fs.glsl",
+            )?,
+            atlas: create_texture_array(tiles)?,
+            chunk_meshes: HashMap::new(),
             mvp: glm::Mat4::identity(),
+            animated_tiles: Vec::new(),
         })
     }
 
-    fn create_vao() -> GLuint {
-        let mut vao = 0;
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
-        }
-        vao
+    /// The block textures, one tile per layer
+    pub fn atlas(&self) -> GLuint {
+        self.atlas
     }
 
-    fn create_vbo() -> GLuint {
-        let vertices_size = std::mem::size_of::<GLfloat>() * VERTICES.len();
-        let vertex_bytes =
-            unsafe { std::slice::from_raw_parts(VERTICES.as_ptr() as *const u8, vertices_size) };
-        let mut vbo = 0;
-        let offset = std::mem::size_of::<GLfloat>() as i32;
+    /// Replaces the block textures, such as when switching texture packs
+    pub fn set_tiles(&mut self, tiles: &[RgbaImage]) -> Result<()> {
+        let atlas = create_texture_array(tiles)?;
         unsafe {
-            gl::GenBuffers(1, &mut vbo);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo as _);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                vertices_size as GLsizeiptr,
-                vertex_bytes.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 5 * offset, 0 as *const GLvoid);
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                5 * offset,
-                (3 * offset) as *const GLvoid,
-            );
-        };
-        vbo
+            gl::DeleteTextures(1, &self.atlas);
+        }
+        self.atlas = atlas;
+        Ok(())
     }
 
-    fn create_shader_program() -> Result<GLuint> {
-        let vertex_shader = Self::load_shader("assets/shaders/block.vs.glsl", gl::VERTEX_SHADER)?;
-        let fragment_shader =
-            Self::load_shader("assets/shaders/block.fs.glsl", gl::FRAGMENT_SHADER)?;
-        let shaders = [vertex_shader, fragment_shader];
-        unsafe {
-            let program = gl::CreateProgram();
-            for shader in shaders.iter() {
-                gl::AttachShader(program, *shader);
-            }
-            gl::LinkProgram(program);
-            for shader in shaders.iter() {
-                gl::DeleteShader(*shader);
-            }
-            Ok(program)
-        }
+    /// Rebuilds the block shaders if their files changed, returning whether they were
+    pub fn reload_shaders(&mut self) -> Result<bool, ShaderError> {
+        self.shader.reload_if_changed()
     }
 
-    fn load_shader(path: &str, kind: GLuint) -> Result<GLuint> {
-        let shader_source = CString::new(fs::read_to_string(path)?.as_bytes())?;
-        unsafe {
-            let shader = gl::CreateShader(kind);
-            gl::ShaderSource(shader, 1, &shader_source.as_ptr(), std::ptr::null());
-            gl::CompileShader(shader);
-            Self::check_compilation(shader)?;
-            Ok(shader)
+    /// Rebuilds the meshes of changed chunks and drops those of unloaded chunks
+    pub fn update_chunk_meshes(
+        &mut self,
+        world: &World,
+        dirty_chunks: &HashSet<ChunkPosition>,
+        profiler: &mut Profiler,
+    ) {
+        self.chunk_meshes
+            .retain(|position, _| world.chunks.contains_key(position));
+        for position in dirty_chunks.iter() {
+            if let Some(chunk) = world.chunks.get(position) {
+                let start = Instant::now();
+                let mesh = mesh_chunk(chunk, world);
+                let translucent = if mesh.translucent.indices.is_empty() {
+                    None
+                } else {
+                    Some(TranslucentMesh {
+                        gpu: GpuMesh::new(&mesh.translucent),
+                        mesh: mesh.translucent,
+                        sorted_for: None,
+                    })
+                };
+                let opaque = GpuMesh::new(&mesh.opaque);
+                profiler.record_mesh_build(start.elapsed().as_secs_f64());
+                self.chunk_meshes.insert(
+                    *position,
+                    ChunkGpuMesh {
+                        opaque,
+                        translucent,
+                    },
+                );
+            }
         }
     }
 
-    fn check_compilation(id: GLuint) -> Result<()> {
-        let mut success = gl::FALSE as GLint;
-
-        unsafe {
-            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut success);
+    /// Draws the meshes of the chunks inside the frustum, the translucent faces last
+    pub unsafe fn draw_world(
+        &mut self,
+        world: &World,
+        frustum: &Frustum,
+        camera_position: &glm::Vec3,
+        profiler: &mut Profiler,
+    ) -> Result<CullingStats> {
+        gl::UseProgram(self.shader.id());
+
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.atlas);
+
+        let mvp_location = self.shader.uniform_location("mvp")?;
+
+        let (tiles, frames): (Vec<_>, Vec<_>) = self
+            .animated_tiles
+            .iter()
+            .take(MAX_ANIMATED_TILES)
+            .copied()
+            .unzip();
+        gl::Uniform1i(
+            self.shader.uniform_location("animatedTileCount")?,
+            tiles.len() as _,
+        );
+        if !tiles.is_empty() {
+            gl::Uniform1iv(
+                self.shader.uniform_location("animatedTiles")?,
+                tiles.len() as _,
+                tiles.as_ptr(),
+            );
+            gl::Uniform1iv(
+                self.shader.uniform_location("animatedFrames")?,
+                frames.len() as _,
+                frames.as_ptr(),
+            );
         }
 
-        if success == gl::TRUE as GLint {
-            return Ok(());
+        let mut stats = CullingStats::default();
+        let mut translucent_chunks = Vec::new();
+        for (position, mesh) in self.chunk_meshes.iter() {
+            let chunk = match world.chunks.get(position) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            let bounding_box = chunk.bounding_box();
+            if !frustum.intersects(&bounding_box) {
+                stats.culled += 1;
+                continue;
+            }
+            let mvp = glm::translate(&self.mvp, &chunk.position);
+            gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
+            if mesh.opaque.triangle_count() > 0 {
+                profiler.record_draw(mesh.opaque.triangle_count());
+            }
+            mesh.opaque.draw();
+            stats.drawn += 1;
+            if mesh.translucent.is_some() {
+                let center = (bounding_box.min + bounding_box.max) / 2.0;
+                translucent_chunks.push((glm::distance2(&center, camera_position), *position));
+            }
         }
 
-        let mut info_log_length = 0;
-        unsafe {
-            gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut info_log_length);
+        // Translucent faces blend over everything else, farthest first,
+        // without hiding each other in the depth buffer
+        translucent_chunks.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl::DepthMask(gl::FALSE);
+        for (_, position) in translucent_chunks.iter() {
+            let chunk = &world.chunks[position];
+            let translucent = match self
+                .chunk_meshes
+                .get_mut(position)
+                .and_then(|mesh| mesh.translucent.as_mut())
+            {
+                Some(translucent) => translucent,
+                None => continue,
+            };
+            let eye = camera_position - chunk.position;
+            if needs_sorting(translucent.sorted_for.as_ref(), &eye) {
+                translucent.mesh.sort_back_to_front(&eye);
+                translucent.gpu.update_indices(&translucent.mesh.indices);
+                translucent.sorted_for = Some(eye);
+            }
+            let mvp = glm::translate(&self.mvp, &chunk.position);
+            gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
+            profiler.record_draw(translucent.gpu.triangle_count());
+            translucent.gpu.draw();
         }
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::BLEND);
+        Ok(stats)
+    }
+}
 
-        let mut info_log = vec![0; info_log_length as usize];
-        unsafe {
-            gl::GetShaderInfoLog(
-                id,
-                info_log_length,
-                std::ptr::null_mut(),
-                info_log.as_mut_ptr() as *mut GLchar,
-            );
+/// Whether translucent faces sorted for one eye position need sorting again for another.
+/// Small movements rarely change the order, so faces are only sorted again once
+/// the eye enters another block or has moved `RESORT_DISTANCE` since.
+fn needs_sorting(sorted_for: Option<&glm::Vec3>, eye: &glm::Vec3) -> bool {
+    match sorted_for {
+        Some(sorted_for) => {
+            block_position_of(sorted_for) != block_position_of(eye)
+                || glm::distance(sorted_for, eye) > RESORT_DISTANCE
         }
-        eprintln!(
-            "ERROR: Shader compilation failed.\n{}\n",
-            std::str::from_utf8(&info_log)?
-        );
-
-        Ok(())
+        None => true,
     }
+}
 
-    fn create_atlas() -> Result<GLuint> {
-        let atlas_image = image::open("assets/textures/atlas.png")?;
-
-        let mut atlas = 0;
-        unsafe {
-            gl::GenTextures(1, &mut atlas);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D_ARRAY, atlas);
-
-            let dimension = 16;
-            let columns = atlas_image.width() / dimension;
-            let rows = atlas_image.height() / dimension;
-            let number_of_tiles = rows * columns;
+/// Uploads equally sized square tiles into a texture array, one tile per layer
+pub fn create_texture_array(tiles: &[RgbaImage]) -> Result<GLuint> {
+    let dimension = match tiles.first() {
+        Some(tile) => tile.width(),
+        None => bail!("A texture array needs at least one tile"),
+    };
+
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+
+        gl::TexImage3D(
+            gl::TEXTURE_2D_ARRAY,
+            0,
+            gl::RGBA as _,
+            dimension as _,
+            dimension as _,
+            tiles.len() as _,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null() as *const GLvoid,
+        );
 
-            gl::TexImage3D(
+        for (layer, tile) in tiles.iter().enumerate() {
+            gl::TexSubImage3D(
                 gl::TEXTURE_2D_ARRAY,
                 0,
-                gl::RGBA as _,
+                0,
+                0,
+                layer as _,
                 dimension as _,
                 dimension as _,
-                number_of_tiles as _,
-                0,
+                1,
                 gl::RGBA,
                 gl::UNSIGNED_BYTE,
-                std::ptr::null() as *const GLvoid,
-            );
-
-            for row in 0..rows {
-                let y = row * dimension;
-                for column in 0..columns {
-                    let x = column * dimension;
-                    let pixels = atlas_image.view(x, y, dimension, dimension).to_image();
-                    let pixel_bytes = pixels.as_bytes();
-                    let tile = (row * columns) + column;
-                    gl::TexSubImage3D(
-                        gl::TEXTURE_2D_ARRAY,
-                        0,
-                        0,
-                        0,
-                        tile as _,
-                        dimension as _,
-                        dimension as _,
-                        1,
-                        gl::RGBA,
-                        gl::UNSIGNED_BYTE,
-                        pixel_bytes.as_ptr() as *const GLvoid,
-                    );
-                }
-            }
-
-            gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
-
-            gl::TexParameterf(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_MAG_FILTER,
-                gl::NEAREST as _,
-            );
-            gl::TexParameterf(
-                gl::TEXTURE_2D_ARRAY,
-                gl::TEXTURE_MIN_FILTER,
-                gl::NEAREST as _,
+                tile.as_bytes().as_ptr() as *const GLvoid,
             );
         }
 
-        Ok(atlas)
-    }
+        gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
 
-    pub unsafe fn draw_world(&self, world: &World) -> Result<()> {
-        for (row_index, row) in world.chunks.iter().enumerate() {
-            for (column_index, chunk) in row.iter().enumerate() {
-                for x in 0..CHUNK_WIDTH {
-                    for z in 0..CHUNK_LENGTH {
-                        for y in 0..CHUNK_DEPTH {
-                            let block = &chunk.blocks[x][z][y];
-
-                            if Block::Air == *block {
-                                return Ok(());
-                            }
-
-                            let configuration = block.configuration();
-
-                            gl::UseProgram(self.shader_program);
-
-                            gl::ActiveTexture(gl::TEXTURE0);
-                            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.atlas);
-
-                            let mvp_location = Self::uniform_location(self.shader_program, "mvp")?;
-                            let id_location =
-                                Self::uniform_location(self.shader_program, "blockId")?;
-
-                            gl::BindVertexArray(self.vao);
-
-                            let mvp = glm::translate(&self.mvp, &chunk.position);
-                            let mvp = glm::translate(&mvp, &glm::vec3(x as _, y as _, z as _));
-                            gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
-
-                            if configuration.is_entity {
-                                // center the quad
-                                let mvp = glm::translate(&mvp, &glm::vec3(0.0, 0.0, -0.5));
-                                gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
-
-                                // front
-                                gl::Uniform1i(id_location, configuration.front);
-                                gl::DrawArrays(gl::TRIANGLES, 6, 6);
-
-                                // rotate and draw the quad
-                                let mvp = glm::rotate(&mvp, -90_f32.to_radians(), &glm::Vec3::y());
-                                let mvp = glm::translate(&mvp, &glm::vec3(0.0, 0.0, -0.5));
-                                gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
-
-                                gl::DrawArrays(gl::TRIANGLES, 6, 6);
-                            } else {
-                                gl::UniformMatrix4fv(mvp_location, 1, gl::FALSE, mvp.as_ptr());
-
-                                // TODO: This doesn't handle checking for solids across chunk borders
-
-                                // back
-                                let should_render =
-                                    if let Some(neighbor) = chunk.blocks[x].get(z - 1) {
-                                        !neighbor[y].configuration().is_solid
-                                    } else {
-                                        true
-                                    };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.back);
-                                    gl::DrawArrays(gl::TRIANGLES, 0, 6);
-                                }
-
-                                // front
-                                let should_render =
-                                    if let Some(neighbor) = chunk.blocks[x].get(z + 1) {
-                                        !neighbor[y].configuration().is_solid
-                                    } else {
-                                        true
-                                    };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.front);
-                                    gl::DrawArrays(gl::TRIANGLES, 6, 6);
-                                }
-
-                                // left
-                                let should_render = if let Some(neighbor) = chunk.blocks.get(x - 1)
-                                {
-                                    !neighbor[z][y].configuration().is_solid
-                                } else {
-                                    true
-                                };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.left);
-                                    gl::DrawArrays(gl::TRIANGLES, 12, 6);
-                                }
-
-                                // right
-                                let should_render = if let Some(neighbor) = chunk.blocks.get(x + 1)
-                                {
-                                    !neighbor[z][y].configuration().is_solid
-                                } else {
-                                    true
-                                };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.right);
-                                    gl::DrawArrays(gl::TRIANGLES, 18, 6);
-                                }
-
-                                // bottom
-                                let should_render =
-                                    if let Some(neighbor) = chunk.blocks[x][z].get(y - 1) {
-                                        !neighbor.configuration().is_solid
-                                    } else {
-                                        true
-                                    };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.bottom);
-                                    gl::DrawArrays(gl::TRIANGLES, 24, 6);
-                                }
-
-                                // top
-                                let should_render =
-                                    if let Some(neighbor) = chunk.blocks[x][z].get(y + 1) {
-                                        !neighbor.configuration().is_solid
-                                    } else {
-                                        true
-                                    };
-
-                                if should_render {
-                                    gl::Uniform1i(id_location, configuration.top);
-                                    gl::DrawArrays(gl::TRIANGLES, 30, 6);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        Ok(())
+        gl::TexParameterf(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MAG_FILTER,
+            gl::NEAREST as _,
+        );
+        gl::TexParameterf(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MIN_FILTER,
+            gl::NEAREST as _,
+        );
     }
 
-    fn uniform_location(shader_program: GLuint, name: &str) -> Result<GLint> {
-        let name: CString = CString::new(name.as_bytes())?;
-        unsafe { Ok(gl::GetUniformLocation(shader_program, name.as_ptr())) }
-    }
+    Ok(texture)
 }
 
 impl Drop for Cube {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteBuffers(1, &self.vbo);
-            gl::DeleteProgram(self.shader_program);
             gl::DeleteTextures(1, &self.atlas);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translucent_faces_are_sorted_once_the_eye_moves_away() {
+        let sorted_for = glm::vec3(4.5, 10.5, 4.5);
+        assert!(needs_sorting(None, &sorted_for));
+        assert!(!needs_sorting(Some(&sorted_for), &sorted_for));
+        assert!(!needs_sorting(
+            Some(&sorted_for),
+            &glm::vec3(4.6, 10.4, 4.75)
+        ));
+        // Into the next block, however little the eye moved
+        assert!(needs_sorting(
+            Some(&glm::vec3(4.99, 10.5, 4.5)),
+            &glm::vec3(5.01, 10.5, 4.5)
+        ));
+        // Across most of a block without leaving it
+        assert!(needs_sorting(
+            Some(&glm::vec3(4.05, 10.05, 4.05)),
+            &glm::vec3(4.95, 10.95, 4.95)
+        ));
+    }
+}
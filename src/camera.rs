@@ -1,5 +1,9 @@
 use nalgebra_glm as glm;
 
+const FIELD_OF_VIEW_DEGREES: f32 = 80.0;
+const NEAR_PLANE: f32 = 0.01;
+const FAR_PLANE: f32 = 1000.0;
+
 pub enum CameraDirection {
     Forward,
     Backward,
@@ -30,7 +34,7 @@ impl Default for FreeCamera {
 impl FreeCamera {
     pub fn new() -> Self {
         let mut camera = Self {
-            position: glm::vec3(0.0, 0.0, 10.0),
+            position: glm::vec3(8.0, 48.0, 24.0),
             right: glm::vec3(0.0, 0.0, 0.0),
             front: glm::vec3(0.0, 0.0, -1.0),
             up: glm::vec3(0.0, 0.0, 0.0),
@@ -44,6 +48,27 @@ impl FreeCamera {
         camera
     }
 
+    pub fn position(&self) -> glm::Vec3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: glm::Vec3) {
+        self.position = position;
+    }
+
+    pub fn front(&self) -> glm::Vec3 {
+        self.front
+    }
+
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> glm::Mat4 {
+        glm::perspective_zo(
+            aspect_ratio,
+            FIELD_OF_VIEW_DEGREES.to_radians(),
+            NEAR_PLANE,
+            FAR_PLANE,
+        )
+    }
+
     pub fn view_matrix(&self) -> glm::Mat4 {
         let target = self.position + self.front;
         glm::look_at(&self.position, &target, &self.up)
@@ -61,20 +86,30 @@ impl FreeCamera {
         };
     }
 
+    /// Points the camera by its yaw and pitch in degrees, where a yaw of -90 looks along -z
+    pub fn set_orientation(&mut self, yaw_degrees: f32, pitch_degrees: f32) {
+        self.yaw_degrees = yaw_degrees;
+        self.pitch_degrees = pitch_degrees;
+        self.clamp_pitch();
+        self.calculate_vectors();
+    }
+
     pub fn process_mouse_movement(&mut self, x_offset: f32, y_offset: f32) {
         let (x_offset, y_offset) = (x_offset * self.sensitivity, y_offset * self.sensitivity);
 
         self.yaw_degrees -= x_offset;
         self.pitch_degrees += y_offset;
+        self.clamp_pitch();
+        self.calculate_vectors();
+    }
 
+    fn clamp_pitch(&mut self) {
         let pitch_threshold = 89.0;
         if self.pitch_degrees > pitch_threshold {
             self.pitch_degrees = pitch_threshold
         } else if self.pitch_degrees < -pitch_threshold {
             self.pitch_degrees = -pitch_threshold
         }
-
-        self.calculate_vectors();
     }
 
     fn calculate_vectors(&mut self) {
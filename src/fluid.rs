@@ -0,0 +1,369 @@
+use nalgebra_glm as glm;
+use std::collections::HashMap;
+
+use crate::{
+    block::{Block, RenderLayer},
+    light::MAX_LIGHT,
+    world::{World, NEIGHBORS},
+};
+
+/// The level of a fluid block fed from above, which spreads like one next to a source
+const FALLING_LEVEL: u8 = 1;
+
+const SIDEWAYS: [[i32; 3]; 4] = [[-1, 0, 0], [1, 0, 0], [0, 0, -1], [0, 0, 1]];
+
+/// The kinds of blocks that flow out from a source block.
+/// Each fluid block has a level counting how far it has flowed, with sources at 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    pub const ALL: [Fluid; 2] = [Fluid::Water, Fluid::Lava];
+
+    /// The highest level, which is also how far the fluid spreads across flat ground
+    pub fn max_level(&self) -> u8 {
+        match self {
+            Fluid::Water => 7,
+            Fluid::Lava => 3,
+        }
+    }
+
+    /// World ticks between a fluid block or its neighbors changing and it flowing again
+    pub fn flow_delay(&self) -> u64 {
+        match self {
+            Fluid::Water => 15,
+            Fluid::Lava => 60,
+        }
+    }
+
+    /// How far sideways the fluid looks for a way down before spreading out evenly
+    fn slope_distance(&self) -> u32 {
+        match self {
+            Fluid::Water => 4,
+            Fluid::Lava => 2,
+        }
+    }
+
+    pub fn render_layer(&self) -> RenderLayer {
+        match self {
+            Fluid::Water => RenderLayer::Translucent,
+            Fluid::Lava => RenderLayer::Opaque,
+        }
+    }
+
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            Fluid::Water => 0,
+            Fluid::Lava => MAX_LIGHT,
+        }
+    }
+
+    pub fn block(&self, level: u8) -> Block {
+        match self {
+            Fluid::Water => Block::Water(level),
+            Fluid::Lava => Block::Lava(level),
+        }
+    }
+
+    /// The fluid that hardens into cobblestone where this one touches it
+    fn opposite(&self) -> Fluid {
+        match self {
+            Fluid::Water => Fluid::Lava,
+            Fluid::Lava => Fluid::Water,
+        }
+    }
+}
+
+/// Fluid blocks waiting to flow, each with the world tick it's due on
+#[derive(Debug, Default)]
+pub struct FluidSchedule {
+    due: HashMap<glm::IVec3, u64>,
+}
+
+impl FluidSchedule {
+    /// Schedules a position to flow on a tick, unless it's already due sooner
+    pub fn schedule(&mut self, position: &glm::IVec3, tick: u64) {
+        let due = self.due.entry(*position).or_insert(tick);
+        *due = (*due).min(tick);
+    }
+
+    /// Removes the positions that are due by a tick, bottom up and in
+    /// a fixed order within each layer so fluids flow the same way every run
+    pub fn take_due(&mut self, tick: u64) -> Vec<glm::IVec3> {
+        let mut positions = self
+            .due
+            .iter()
+            .filter(|(_, due)| **due <= tick)
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        for position in positions.iter() {
+            self.due.remove(position);
+        }
+        positions.sort_by_key(|position| (position.y, position.x, position.z));
+        positions
+    }
+}
+
+/// Runs one step of the fluid at a position.
+///
+/// Lava touching water hardens into cobblestone. Flowing blocks take their level
+/// from the fluid feeding them and dry up without any. Otherwise the fluid falls
+/// if it can, or spreads sideways one level higher, preferring the directions with
+/// the nearest way down. Every block changed this way schedules its neighbors,
+/// so the flow carries on over the following ticks until it settles.
+pub fn flow(world: &mut World, position: &glm::IVec3) {
+    let (fluid, level) = match world.block_at(position).and_then(|block| block.fluid()) {
+        Some(fluid) => fluid,
+        None => return,
+    };
+
+    if fluid == Fluid::Lava && touches(world, position, Fluid::Water) {
+        world.set_block(position, Block::Cobblestone);
+        return;
+    }
+
+    if level > 0 {
+        match fed_level(world, position, fluid) {
+            Some(fed) if fed == level => {}
+            Some(fed) => {
+                world.set_block(position, fluid.block(fed));
+                return;
+            }
+            None => {
+                world.set_block(position, Block::Air);
+                return;
+            }
+        }
+    }
+
+    let below = position - glm::vec3(0, 1, 0);
+    if holds(world, &below, fluid) || flow_into(world, &below, fluid, FALLING_LEVEL) {
+        return;
+    }
+
+    let next_level = level + 1;
+    if next_level > fluid.max_level() {
+        return;
+    }
+    let targets = SIDEWAYS
+        .iter()
+        .map(|direction| glm::IVec3::from(*direction))
+        .filter(|direction| can_flow_into(world, &(position + direction), fluid, next_level))
+        .map(|direction| {
+            let target = position + direction;
+            let distance = distance_to_drop(world, &target, fluid, &direction, 0);
+            (target, distance)
+        })
+        .collect::<Vec<_>>();
+    let nearest_drop = targets.iter().filter_map(|(_, distance)| *distance).min();
+    for (target, distance) in targets {
+        if nearest_drop.is_none() || distance == nearest_drop {
+            flow_into(world, &target, fluid, next_level);
+        }
+    }
+}
+
+/// The level a flowing block should have, given the fluid around it,
+/// or None if nothing feeds it
+fn fed_level(world: &World, position: &glm::IVec3, fluid: Fluid) -> Option<u8> {
+    if holds(world, &(position + glm::vec3(0, 1, 0)), fluid) {
+        return Some(FALLING_LEVEL);
+    }
+    SIDEWAYS
+        .iter()
+        .filter_map(|direction| {
+            let neighbor = position + glm::IVec3::from(*direction);
+            match world.block_at(&neighbor)?.fluid()? {
+                (other, level) if other == fluid => Some(level + 1),
+                _ => None,
+            }
+        })
+        .min()
+        .filter(|level| *level <= fluid.max_level())
+}
+
+fn holds(world: &World, position: &glm::IVec3, fluid: Fluid) -> bool {
+    match world.block_at(position).and_then(|block| block.fluid()) {
+        Some((other, _)) => other == fluid,
+        None => false,
+    }
+}
+
+fn touches(world: &World, position: &glm::IVec3, fluid: Fluid) -> bool {
+    NEIGHBORS
+        .iter()
+        .any(|direction| holds(world, &(position + glm::IVec3::from(*direction)), fluid))
+}
+
+/// Fluids wash away air and entities, raise the level of the same
+/// fluid further from its source, and meet the opposite fluid
+fn can_flow_into(world: &World, position: &glm::IVec3, fluid: Fluid, level: u8) -> bool {
+    let block = match world.block_at(position) {
+        Some(block) => block,
+        None => return false,
+    };
+    match block.fluid() {
+        Some((other, other_level)) if other == fluid => other_level > level,
+        Some(_) => true,
+        None => block == Block::Air || world.registry.configuration(block).is_entity,
+    }
+}
+
+/// Moves fluid into a position, returning whether it could.
+/// Where water and lava meet, the position hardens into cobblestone instead.
+fn flow_into(world: &mut World, position: &glm::IVec3, fluid: Fluid, level: u8) -> bool {
+    if !can_flow_into(world, position, fluid, level) {
+        return false;
+    }
+    let block = if holds(world, position, fluid.opposite()) {
+        Block::Cobblestone
+    } else {
+        fluid.block(level)
+    };
+    world.set_block(position, block);
+    true
+}
+
+/// How many blocks sideways from a position the fluid could fall, searching
+/// no further than its slope distance and never back the way it came
+fn distance_to_drop(
+    world: &World,
+    position: &glm::IVec3,
+    fluid: Fluid,
+    direction: &glm::IVec3,
+    distance: u32,
+) -> Option<u32> {
+    let below = position - glm::vec3(0, 1, 0);
+    if holds(world, &below, fluid) || can_flow_into(world, &below, fluid, FALLING_LEVEL) {
+        return Some(distance);
+    }
+    if distance == fluid.slope_distance() {
+        return None;
+    }
+    SIDEWAYS
+        .iter()
+        .map(|direction| glm::IVec3::from(*direction))
+        .filter(|next| *next != -direction)
+        .filter(|next| {
+            let neighbor = position + next;
+            holds(world, &neighbor, fluid) || can_flow_into(world, &neighbor, fluid, FALLING_LEVEL)
+        })
+        .filter_map(|next| distance_to_drop(world, &(position + next), fluid, &next, distance + 1))
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        terrain::{FlatTerrainGenerator, TerrainGenerator},
+        world::test_world,
+    };
+
+    const GROUND_HEIGHT: usize = 10;
+    /// The layer of air just above the ground
+    const SURFACE: i32 = GROUND_HEIGHT as i32;
+
+    /// Flat ground in a chunk and its neighbors, so fluids can spread past its edges
+    fn flat_world() -> World {
+        let generator = FlatTerrainGenerator {
+            height: GROUND_HEIGHT,
+        };
+        let mut world = test_world(FlatTerrainGenerator {
+            height: GROUND_HEIGHT,
+        });
+        for x in -1..=1 {
+            for z in -1..=1 {
+                let position = glm::vec2(x, z);
+                world.insert_chunk(position, generator.generate(&position));
+            }
+        }
+        world
+    }
+
+    fn run_ticks(world: &mut World, ticks: u32) {
+        for _ in 0..ticks {
+            world.tick();
+        }
+    }
+
+    /// A row of blocks along x, with a letter for solid blocks, '.' for air
+    /// and the level of water
+    fn row(world: &World, y: i32, z: i32) -> String {
+        (0..16)
+            .map(|x| match world.block_at(&glm::vec3(x, y, z)).unwrap() {
+                Block::Air => '.',
+                Block::Water(level) => (b'0' + level) as char,
+                Block::Cobblestone => 'C',
+                _ => '#',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn water_spreads_to_its_max_level_and_stops() {
+        let mut world = flat_world();
+        world.set_block(&glm::vec3(8, SURFACE, 8), Block::Water(0));
+        run_ticks(&mut world, 20 * Fluid::Water.flow_delay() as u32);
+        assert_eq!(row(&world, SURFACE, 8), ".765432101234567");
+        assert_eq!(row(&world, SURFACE, 1), "........7.......");
+        assert_eq!(row(&world, SURFACE + 1, 8), "................");
+        assert_eq!(world.block_at(&glm::vec3(8, SURFACE, 0)), Some(Block::Air));
+
+        let settled = world.chunks[&glm::vec2(0, 0)].blocks;
+        run_ticks(&mut world, 10 * Fluid::Water.flow_delay() as u32);
+        assert!(world.chunks[&glm::vec2(0, 0)].blocks == settled);
+    }
+
+    #[test]
+    fn flowing_water_dries_up_without_its_source() {
+        let mut world = flat_world();
+        world.set_block(&glm::vec3(8, SURFACE, 8), Block::Water(0));
+        run_ticks(&mut world, 20 * Fluid::Water.flow_delay() as u32);
+        world.set_block(&glm::vec3(8, SURFACE, 8), Block::Air);
+        run_ticks(&mut world, 20 * Fluid::Water.flow_delay() as u32);
+        for z in 0..16 {
+            assert_eq!(row(&world, SURFACE, z), "................", "z = {}", z);
+        }
+    }
+
+    #[test]
+    fn lava_touching_water_hardens() {
+        let mut world = flat_world();
+        world.set_block(&glm::vec3(8, SURFACE, 8), Block::Lava(0));
+        world.set_block(&glm::vec3(9, SURFACE, 8), Block::Water(0));
+        run_ticks(&mut world, Fluid::Lava.flow_delay() as u32);
+        assert_eq!(
+            world.block_at(&glm::vec3(8, SURFACE, 8)),
+            Some(Block::Cobblestone)
+        );
+        assert_eq!(
+            world.block_at(&glm::vec3(9, SURFACE, 8)),
+            Some(Block::Water(0))
+        );
+    }
+
+    #[test]
+    fn water_falls_before_spreading() {
+        let mut world = flat_world();
+        let top = SURFACE + 6;
+        world.set_block(&glm::vec3(8, top, 8), Block::Water(0));
+        run_ticks(&mut world, 20 * Fluid::Water.flow_delay() as u32);
+        for y in SURFACE..top {
+            assert_eq!(
+                world.block_at(&glm::vec3(8, y, 8)),
+                Some(Block::Water(FALLING_LEVEL)),
+                "y = {}",
+                y
+            );
+        }
+        for y in SURFACE + 1..=top {
+            assert_eq!(row(&world, y, 7), "................", "y = {}", y);
+        }
+        // Only spreading out once it lands, as if next to a source
+        assert_eq!(row(&world, SURFACE, 8), "..7654321234567.");
+    }
+}
@@ -0,0 +1,131 @@
+use nalgebra_glm as glm;
+
+use crate::aabb::Aabb;
+
+/// A plane with points satisfying `normal · point + distance = 0`.
+/// Points on the side the normal faces have a positive distance to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// Creates a plane from the coefficients of its equation, normalizing them
+    fn from_coefficients(coefficients: &glm::Vec4) -> Self {
+        let normal = coefficients.xyz();
+        let length = normal.norm();
+        Self {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: &glm::Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// The volume visible to a camera, bounded by six planes facing inward
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum planes from a view-projection matrix,
+    /// using the zero to one clip space depth range of `glm::perspective_zo`
+    pub fn from_view_projection(view_projection: &glm::Mat4) -> Self {
+        let row = |index: usize| -> glm::Vec4 { view_projection.row(index).transpose() };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [
+                Plane::from_coefficients(&(w + x)),
+                Plane::from_coefficients(&(w - x)),
+                Plane::from_coefficients(&(w + y)),
+                Plane::from_coefficients(&(w - y)),
+                Plane::from_coefficients(&z),
+                Plane::from_coefficients(&(w - z)),
+            ],
+        }
+    }
+
+    /// Whether any part of a box may be visible.
+    /// Boxes are only rejected when they lie entirely behind one of the planes,
+    /// so some boxes near the frustum's corners are kept even though they are outside.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner of the box furthest along the plane's normal
+            let mut corner = aabb.min;
+            for axis in 0..3 {
+                if plane.normal[axis] >= 0.0 {
+                    corner[axis] = aabb.max[axis];
+                }
+            }
+            plane.signed_distance(&corner) >= 0.0
+        })
+    }
+}
+
+/// How many chunks were drawn and how many were skipped for being off screen
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CullingStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::FreeCamera;
+
+    /// The frustum of a camera at the origin looking along -z
+    fn frustum() -> Frustum {
+        let mut camera = FreeCamera::default();
+        camera.set_position(glm::vec3(0.0, 0.0, 0.0));
+        camera.set_orientation(-90.0, 0.0);
+        let view_projection = camera.projection_matrix(16.0 / 9.0) * camera.view_matrix();
+        Frustum::from_view_projection(&view_projection)
+    }
+
+    fn aabb(min: [f32; 3], max: [f32; 3]) -> Aabb {
+        Aabb {
+            min: glm::Vec3::from(min),
+            max: glm::Vec3::from(max),
+        }
+    }
+
+    #[test]
+    fn planes_are_normalized() {
+        for plane in frustum().planes.iter() {
+            assert!((plane.normal.norm() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn keeps_boxes_in_front_of_the_camera() {
+        let frustum = frustum();
+        assert!(frustum.intersects(&aabb([-1.0, -1.0, -12.0], [1.0, 1.0, -10.0])));
+        assert!(frustum.intersects(&aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0])));
+        // Partly inside the left edge of the view
+        assert!(frustum.intersects(&aabb([-30.0, -1.0, -12.0], [-10.0, 1.0, -10.0])));
+    }
+
+    #[test]
+    fn culls_boxes_behind_the_camera() {
+        assert!(!frustum().intersects(&aabb([-1.0, -1.0, 10.0], [1.0, 1.0, 12.0])));
+    }
+
+    #[test]
+    fn culls_boxes_beyond_the_far_plane() {
+        assert!(!frustum().intersects(&aabb([-1.0, -1.0, -1100.0], [1.0, 1.0, -1050.0])));
+    }
+
+    #[test]
+    fn culls_boxes_off_to_the_side() {
+        let frustum = frustum();
+        assert!(!frustum.intersects(&aabb([200.0, -1.0, -12.0], [210.0, 1.0, -10.0])));
+        assert!(!frustum.intersects(&aabb([-1.0, 100.0, -12.0], [1.0, 110.0, -10.0])));
+    }
+}
@@ -0,0 +1,341 @@
+use anyhow::{Context, Result};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs};
+
+/// Gamepad buttons, named by their position on the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    /// Pressed once pulled past the trigger threshold
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// The latest state of the connected gamepads, merged together
+#[derive(Debug, Default, Clone)]
+pub struct GamepadState {
+    /// Raw stick positions with each axis in [-1, 1] and up being positive y
+    pub left_stick: glm::Vec2,
+    pub right_stick: glm::Vec2,
+    pub pressed_buttons: HashSet<GamepadButton>,
+    /// Buttons that went down since the last frame
+    pub just_pressed_buttons: HashSet<GamepadButton>,
+    pub just_released_buttons: HashSet<GamepadButton>,
+}
+
+impl GamepadState {
+    pub fn is_button_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn is_button_just_pressed(&self, button: GamepadButton) -> bool {
+        self.just_pressed_buttons.contains(&button)
+    }
+
+    pub fn is_button_just_released(&self, button: GamepadButton) -> bool {
+        self.just_released_buttons.contains(&button)
+    }
+
+    /// Forgets which buttons changed, before the next frame's events are read
+    pub fn new_frame(&mut self) {
+        self.just_pressed_buttons.clear();
+        self.just_released_buttons.clear();
+    }
+
+    pub fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        if pressed {
+            if self.pressed_buttons.insert(button) {
+                self.just_pressed_buttons.insert(button);
+            }
+        } else if self.pressed_buttons.remove(&button) {
+            self.just_released_buttons.insert(button);
+        }
+    }
+
+    /// Presses a trigger once it is pulled past the threshold, with values from 0 to 1.
+    /// Gamepad events are only read with the gamepad feature.
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    pub fn set_trigger(&mut self, button: GamepadButton, value: f32, threshold: f32) {
+        self.set_button(button, value >= threshold);
+    }
+}
+
+/// How a stick's raw position is turned into a response
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct StickSettings {
+    /// Deflection below which the stick is treated as centered, from 0 to 1
+    pub dead_zone: f32,
+    /// The response curve past the dead zone. 1 is linear and larger values
+    /// give finer control near the center.
+    pub exponent: f32,
+}
+
+impl StickSettings {
+    /// Applies a radial dead zone and the response curve to a stick position.
+    /// The result points the same way as the stick with a length from 0 to 1,
+    /// starting at 0 right at the edge of the dead zone.
+    pub fn apply(&self, stick: &glm::Vec2) -> glm::Vec2 {
+        let magnitude = stick.norm();
+        if magnitude <= self.dead_zone || magnitude == 0.0 {
+            return glm::Vec2::zeros();
+        }
+        let live_range = (1.0 - self.dead_zone).max(f32::EPSILON);
+        let scaled = ((magnitude.min(1.0) - self.dead_zone) / live_range).powf(self.exponent);
+        stick / magnitude * scaled
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GamepadSettings {
+    pub movement_stick: StickSettings,
+    pub look_stick: StickSettings,
+    /// Look speed at full deflection in the units of mouse movement per second,
+    /// so the mouse sensitivity applies to both
+    pub look_speed: f32,
+    pub invert_look: bool,
+    /// How far a trigger has to be pulled to count as pressed, from 0 to 1
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    pub trigger_threshold: f32,
+}
+
+impl GamepadSettings {
+    pub fn load(path: &str) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read gamepad settings '{}'", path))?;
+        ron::from_str(&source)
+            .with_context(|| format!("Failed to parse gamepad settings '{}'", path))
+    }
+
+    /// Forward and rightward movement from the left stick, as in `PlayerInput::movement`
+    pub fn movement(&self, state: &GamepadState) -> glm::Vec2 {
+        let stick = self.movement_stick.apply(&state.left_stick);
+        glm::vec2(stick.y, stick.x)
+    }
+
+    /// Camera rotation from the right stick over a frame,
+    /// as offsets for `FreeCamera::process_mouse_movement`
+    pub fn look_offset(&self, state: &GamepadState, delta_time: f32) -> glm::Vec2 {
+        let stick = self.look_stick.apply(&state.right_stick) * self.look_speed * delta_time;
+        let vertical = if self.invert_look { -stick.y } else { stick.y };
+        glm::vec2(-stick.x, vertical)
+    }
+}
+
+/// Reads gamepad events into a `GamepadState` once per frame.
+/// Without the `gamepad` feature no gamepads are ever connected.
+pub struct GamepadPoller {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Default for GamepadPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GamepadPoller {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "gamepad")]
+            gilrs: match gilrs::Gilrs::new() {
+                Ok(gilrs) => Some(gilrs),
+                Err(error) => {
+                    eprintln!("Gamepads are unavailable: {}", error);
+                    None
+                }
+            },
+        }
+    }
+
+    #[cfg_attr(not(feature = "gamepad"), allow(unused_variables))]
+    pub fn poll(&mut self, state: &mut GamepadState, settings: &GamepadSettings) {
+        state.new_frame();
+        #[cfg(feature = "gamepad")]
+        self.read_events(state, settings);
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn read_events(&mut self, state: &mut GamepadState, settings: &GamepadSettings) {
+        use gilrs::{Axis, Button, EventType};
+
+        let gilrs = match self.gilrs.as_mut() {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    state.set_trigger(
+                        GamepadButton::LeftTrigger,
+                        value,
+                        settings.trigger_threshold,
+                    );
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    state.set_trigger(
+                        GamepadButton::RightTrigger,
+                        value,
+                        settings.trigger_threshold,
+                    );
+                }
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = gamepad_button(button) {
+                        state.set_button(button, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = gamepad_button(button) {
+                        state.set_button(button, false);
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => match axis {
+                    Axis::LeftStickX => state.left_stick.x = value,
+                    Axis::LeftStickY => state.left_stick.y = value,
+                    Axis::RightStickX => state.right_stick.x = value,
+                    Axis::RightStickY => state.right_stick.y = value,
+                    _ => {}
+                },
+                EventType::Disconnected => *state = GamepadState::default(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Maps gilrs buttons to ours. Analog triggers are handled separately.
+#[cfg(feature = "gamepad")]
+fn gamepad_button(button: gilrs::Button) -> Option<GamepadButton> {
+    use gilrs::Button;
+
+    let button = match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> GamepadSettings {
+        GamepadSettings::load("assets/gamepad.ron").unwrap()
+    }
+
+    fn stick(dead_zone: f32, exponent: f32) -> StickSettings {
+        StickSettings {
+            dead_zone,
+            exponent,
+        }
+    }
+
+    #[test]
+    fn dead_zone_centers_the_stick() {
+        let stick = stick(0.2, 1.0);
+        assert_eq!(stick.apply(&glm::vec2(0.0, 0.0)), glm::Vec2::zeros());
+        assert_eq!(stick.apply(&glm::vec2(0.1, -0.1)), glm::Vec2::zeros());
+        assert_eq!(stick.apply(&glm::vec2(0.0, 0.2)), glm::Vec2::zeros());
+    }
+
+    #[test]
+    fn full_deflection_has_length_one() {
+        let stick = stick(0.2, 2.0);
+        for position in [
+            glm::vec2(1.0, 0.0),
+            glm::vec2(0.0, -1.0),
+            glm::vec2(0.6, 0.8),
+            // Some gamepads report corners past the unit circle
+            glm::vec2(1.0, 1.0),
+        ] {
+            let response = stick.apply(&position);
+            assert!((response.norm() - 1.0).abs() < 1e-6, "{:?}", position);
+            assert!((response.normalize() - position.normalize()).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn exponent_softens_small_movements() {
+        let position = glm::vec2(0.6, 0.0);
+        let linear = stick(0.2, 1.0).apply(&position);
+        let curved = stick(0.2, 2.0).apply(&position);
+        assert!((linear.x - 0.5).abs() < 1e-6);
+        assert!((curved.x - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn left_stick_moves_forward_and_right() {
+        let settings = settings();
+        let mut state = GamepadState {
+            left_stick: glm::vec2(0.0, 1.0),
+            ..Default::default()
+        };
+        assert_eq!(settings.movement(&state), glm::vec2(1.0, 0.0));
+        state.left_stick = glm::vec2(1.0, 0.0);
+        assert_eq!(settings.movement(&state), glm::vec2(0.0, 1.0));
+        state.left_stick = glm::vec2(0.1, 0.1);
+        assert_eq!(settings.movement(&state), glm::Vec2::zeros());
+    }
+
+    #[test]
+    fn right_stick_looks_around() {
+        let mut settings = settings();
+        let mut state = GamepadState {
+            right_stick: glm::vec2(1.0, 1.0).normalize(),
+            ..Default::default()
+        };
+        let offset = settings.look_offset(&state, 0.5);
+        let expected = settings.look_speed * 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        assert!((offset.x + expected).abs() < 1e-3);
+        assert!((offset.y - expected).abs() < 1e-3);
+
+        settings.invert_look = true;
+        let inverted = settings.look_offset(&state, 0.5);
+        assert_eq!(inverted, glm::vec2(offset.x, -offset.y));
+
+        state.right_stick = glm::vec2(0.1, 0.0);
+        assert_eq!(settings.look_offset(&state, 0.5), glm::Vec2::zeros());
+    }
+
+    #[test]
+    fn triggers_press_past_the_threshold() {
+        let mut state = GamepadState::default();
+        state.set_trigger(GamepadButton::RightTrigger, 0.3, 0.5);
+        assert!(!state.is_button_pressed(GamepadButton::RightTrigger));
+        state.set_trigger(GamepadButton::RightTrigger, 0.7, 0.5);
+        assert!(state.is_button_just_pressed(GamepadButton::RightTrigger));
+        state.new_frame();
+        state.set_trigger(GamepadButton::RightTrigger, 0.9, 0.5);
+        assert!(state.is_button_pressed(GamepadButton::RightTrigger));
+        assert!(!state.is_button_just_pressed(GamepadButton::RightTrigger));
+        state.set_trigger(GamepadButton::RightTrigger, 0.1, 0.5);
+        assert!(state.is_button_just_released(GamepadButton::RightTrigger));
+    }
+}
@@ -0,0 +1,578 @@
+use anyhow::{Context, Result};
+use image::{Rgba, RgbaImage};
+use nalgebra_glm as glm;
+use std::time::Instant;
+
+use crate::{
+    app::{load_registry, starting_inventory, App, WORLD_SEED},
+    camera::FreeCamera,
+    frustum::{CullingStats, Frustum},
+    hud::{layout_hud, HudQuad, QuadTexture, FONT_PATH, GLYPH_SIZE},
+    light::brightness,
+    mesh::{ambient_occlusion_brightness, mesh_chunk, Mesh, Vertex},
+    profiler::Profiler,
+    replay::Recording,
+    texture_pack::{load_pack_layers, split_tiles},
+    world::World,
+};
+
+/// Renders a newly generated world from a camera without opening a window and writes it
+/// as a PNG. Saved worlds are ignored so the same arguments always give the same image.
+pub fn render_screenshot(
+    path: &str,
+    dimensions: [u32; 2],
+    camera: &FreeCamera,
+    texture_pack: &str,
+) -> Result<()> {
+    let mut world = World::new(WORLD_SEED, load_registry()?);
+    world.update(&camera.position());
+    world.finish_loading();
+    let inventory = starting_inventory(&world.registry);
+    let hud = layout_hud(dimensions, &inventory, &world.registry, &[]);
+    render_view(&world, camera, &hud, &[], path, dimensions, texture_pack)
+}
+
+/// Replays a recording without a window, reporting where the camera ended up,
+/// and optionally renders the final frame and writes the frame statistics as CSV
+pub fn replay(
+    recording_path: &str,
+    screenshot_path: Option<&str>,
+    profile_path: Option<&str>,
+    texture_pack: &str,
+) -> Result<()> {
+    let recording = Recording::load(recording_path)?;
+    let mut app = App::replaying(&recording)?;
+    for frame in recording.frames.iter() {
+        app.replay_frame(frame)?;
+    }
+    let position = app.camera().position();
+    println!(
+        "Replayed {} frames, the camera ended at ({:.3}, {:.3}, {:.3})",
+        recording.frames.len(),
+        position.x,
+        position.y,
+        position.z
+    );
+    if let Some(path) = profile_path {
+        app.profiler().save_csv(path)?;
+    }
+    match screenshot_path {
+        Some(path) => render_view(
+            app.world(),
+            app.camera(),
+            &app.hud_layout(),
+            &app.animated_tiles(),
+            path,
+            recording.window_dimensions,
+            texture_pack,
+        ),
+        None => Ok(()),
+    }
+}
+
+fn render_view(
+    world: &World,
+    camera: &FreeCamera,
+    hud: &[HudQuad],
+    animated_tiles: &[(i32, i32)],
+    path: &str,
+    dimensions: [u32; 2],
+    texture_pack: &str,
+) -> Result<()> {
+    let aspect_ratio = dimensions[0] as f32 / dimensions[1] as f32;
+    let view_projection = camera.projection_matrix(aspect_ratio) * camera.view_matrix();
+
+    let mut renderer = SoftwareRenderer::new(
+        dimensions[0],
+        dimensions[1],
+        load_pack_layers(texture_pack)?,
+        FONT_PATH,
+    )?;
+    renderer.animated_tiles = animated_tiles.to_vec();
+    let mut profiler = Profiler::new();
+    let start = Instant::now();
+    renderer.clear();
+    let stats = renderer.render_world(world, &view_projection, &camera.position(), &mut profiler);
+    renderer.draw_hud(hud);
+    profiler.finish_frame(start.elapsed().as_secs_f64(), world.chunks.len());
+    println!(
+        "Drew {} chunks and culled {} outside the view",
+        stats.drawn, stats.culled
+    );
+    for line in profiler.summary() {
+        println!("{}", line);
+    }
+    renderer.save(path)
+}
+
+/// A clip space vertex along with the attributes interpolated across a triangle
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    position: glm::Vec4,
+    uv: glm::Vec2,
+    light: glm::Vec2,
+    ambient_occlusion: f32,
+}
+
+impl ClipVertex {
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        ClipVertex {
+            position: self.position + (other.position - self.position) * t,
+            uv: self.uv + (other.uv - self.uv) * t,
+            light: self.light + (other.light - self.light) * t,
+            ambient_occlusion: self.ambient_occlusion
+                + (other.ambient_occlusion - self.ambient_occlusion) * t,
+        }
+    }
+}
+
+/// Renders worlds on the CPU, mirroring the block shaders,
+/// so frames can be produced on machines without a display or GPU
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    /// Block textures, one tile per texture array layer
+    tiles: Vec<RgbaImage>,
+    glyphs: Vec<RgbaImage>,
+    color: RgbaImage,
+    depth: Vec<f32>,
+    pub background: Rgba<u8>,
+    /// Animated tiles paired with the layers of their current frames, as in `Cube`
+    pub animated_tiles: Vec<(i32, i32)>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32, tiles: Vec<RgbaImage>, font_path: &str) -> Result<Self> {
+        let font = image::open(font_path)
+            .with_context(|| format!("Failed to open font '{}'", font_path))?
+            .to_rgba8();
+        Ok(Self {
+            width,
+            height,
+            tiles,
+            glyphs: split_tiles(&font, GLYPH_SIZE),
+            color: RgbaImage::new(width, height),
+            depth: vec![f32::INFINITY; (width * height) as usize],
+            background: Rgba([64, 64, 64, 255]),
+            animated_tiles: Vec::new(),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in self.color.pixels_mut() {
+            *pixel = self.background;
+        }
+        for depth in self.depth.iter_mut() {
+            *depth = f32::INFINITY;
+        }
+    }
+
+    /// Draws the chunks inside the view like `Cube::draw_world`,
+    /// blending the translucent faces over the rest from back to front
+    pub fn render_world(
+        &mut self,
+        world: &World,
+        view_projection: &glm::Mat4,
+        camera_position: &glm::Vec3,
+        profiler: &mut Profiler,
+    ) -> CullingStats {
+        let frustum = Frustum::from_view_projection(view_projection);
+        let mut stats = CullingStats::default();
+        let mut translucent_meshes = Vec::new();
+        for chunk in world.chunks.values() {
+            let bounding_box = chunk.bounding_box();
+            if !frustum.intersects(&bounding_box) {
+                stats.culled += 1;
+                continue;
+            }
+            stats.drawn += 1;
+            let mesh_start = Instant::now();
+            let mesh = mesh_chunk(chunk, world);
+            profiler.record_mesh_build(mesh_start.elapsed().as_secs_f64());
+            profiler.record_draw(mesh.opaque.indices.len() / 3);
+            let mvp = glm::translate(view_projection, &chunk.position);
+            self.draw_mesh(&mvp, &mesh.opaque, false);
+            if !mesh.translucent.indices.is_empty() {
+                let center = (bounding_box.min + bounding_box.max) / 2.0;
+                let distance = glm::distance2(&center, camera_position);
+                translucent_meshes.push((distance, chunk.position, mesh.translucent));
+            }
+        }
+
+        translucent_meshes.sort_by(|(a, _, _), (b, _, _)| b.total_cmp(a));
+        for (_, position, mut mesh) in translucent_meshes {
+            mesh.sort_back_to_front(&(camera_position - position));
+            profiler.record_draw(mesh.indices.len() / 3);
+            let mvp = glm::translate(view_projection, &position);
+            self.draw_mesh(&mvp, &mesh, true);
+        }
+        stats
+    }
+
+    fn draw_mesh(&mut self, mvp: &glm::Mat4, mesh: &Mesh, blend: bool) {
+        for triangle in mesh.indices.chunks(3) {
+            let vertices = [
+                mesh.vertices[triangle[0] as usize],
+                mesh.vertices[triangle[1] as usize],
+                mesh.vertices[triangle[2] as usize],
+            ];
+            self.draw_triangle(mvp, &vertices, blend);
+        }
+    }
+
+    /// Blends HUD quads over the frame in order, mirroring hud.fs.glsl
+    pub fn draw_hud(&mut self, quads: &[HudQuad]) {
+        for quad in quads.iter() {
+            let size = quad.max - quad.min;
+            let start_x = quad.min.x.max(0.0) as u32;
+            let end_x = quad.max.x.min(self.width as f32).max(0.0) as u32;
+            let start_y = quad.min.y.max(0.0) as u32;
+            let end_y = quad.max.y.min(self.height as f32).max(0.0) as u32;
+            for y in start_y..end_y {
+                for x in start_x..end_x {
+                    let u = (x as f32 + 0.5 - quad.min.x) / size.x;
+                    let v = (y as f32 + 0.5 - quad.min.y) / size.y;
+                    let texel = match quad.texture {
+                        QuadTexture::None => Rgba([255, 255, 255, 255]),
+                        QuadTexture::Tile(tile) => sample_layer(&self.tiles, tile, u, v),
+                        QuadTexture::Glyph(glyph) => sample_layer(&self.glyphs, glyph as i32, u, v),
+                    };
+                    let color = glm::vec4(
+                        texel[0] as f32,
+                        texel[1] as f32,
+                        texel[2] as f32,
+                        texel[3] as f32,
+                    ) / 255.0;
+                    let color = color.component_mul(&quad.color);
+                    if color.w == 0.0 {
+                        continue;
+                    }
+                    let pixel = self.color.get_pixel_mut(x, y);
+                    for channel in 0..3 {
+                        let blended = color[channel] * 255.0 * color.w
+                            + pixel[channel] as f32 * (1.0 - color.w);
+                        pixel[channel] = blended.round() as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        self.color
+            .save(path)
+            .with_context(|| format!("Failed to write screenshot '{}'", path))
+    }
+
+    fn draw_triangle(&mut self, mvp: &glm::Mat4, vertices: &[Vertex; 3], blend: bool) {
+        let clip = vertices.iter().map(|vertex| ClipVertex {
+            position: mvp
+                * glm::vec4(
+                    vertex.position[0],
+                    vertex.position[1],
+                    vertex.position[2],
+                    1.0,
+                ),
+            uv: glm::vec2(vertex.uv[0], vertex.uv[1]),
+            light: glm::vec2(vertex.light[0], vertex.light[1]),
+            ambient_occlusion: vertex.ambient_occlusion,
+        });
+        let polygon = clip_near(&clip.collect::<Vec<_>>());
+        let layer = vertices[0].layer;
+        for index in 1..polygon.len().saturating_sub(1) {
+            self.rasterize(
+                &[polygon[0], polygon[index], polygon[index + 1]],
+                layer,
+                blend,
+            );
+        }
+    }
+
+    /// Fills a triangle, either writing depth or blending over the frame without it
+    fn rasterize(&mut self, triangle: &[ClipVertex; 3], layer: i32, blend: bool) {
+        // Screen space x and y, normalized depth, and 1 / w for perspective correction
+        let screen = triangle
+            .iter()
+            .map(|vertex| {
+                let inverse_w = 1.0 / vertex.position.w;
+                let ndc = vertex.position.xyz() * inverse_w;
+                glm::vec4(
+                    (ndc.x * 0.5 + 0.5) * self.width as f32,
+                    (0.5 - ndc.y * 0.5) * self.height as f32,
+                    ndc.z,
+                    inverse_w,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let area = edge(&screen[0], &screen[1], &screen[2]);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let min_x = screen.iter().map(|v| v.x).fold(f32::INFINITY, f32::min);
+        let max_x = screen.iter().map(|v| v.x).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = screen.iter().map(|v| v.y).fold(f32::INFINITY, f32::min);
+        let max_y = screen.iter().map(|v| v.y).fold(f32::NEG_INFINITY, f32::max);
+        let start_x = min_x.floor().max(0.0) as u32;
+        let end_x = (max_x.ceil().min(self.width as f32)) as u32;
+        let start_y = min_y.floor().max(0.0) as u32;
+        let end_y = (max_y.ceil().min(self.height as f32)) as u32;
+
+        for y in start_y..end_y {
+            for x in start_x..end_x {
+                let point = glm::vec4(x as f32 + 0.5, y as f32 + 0.5, 0.0, 0.0);
+                let weights = [
+                    edge(&screen[1], &screen[2], &point) / area,
+                    edge(&screen[2], &screen[0], &point) / area,
+                    edge(&screen[0], &screen[1], &point) / area,
+                ];
+                if weights.iter().any(|weight| *weight < 0.0) {
+                    continue;
+                }
+
+                let depth: f32 = (0..3).map(|i| weights[i] * screen[i].z).sum();
+                let index = (y * self.width + x) as usize;
+                if !(0.0..=1.0).contains(&depth) || depth >= self.depth[index] {
+                    continue;
+                }
+
+                let inverse_w: f32 = (0..3).map(|i| weights[i] * screen[i].w).sum();
+                let interpolate = |attribute: fn(&ClipVertex) -> glm::Vec2| {
+                    (0..3)
+                        .map(|i| attribute(&triangle[i]) * (weights[i] * screen[i].w))
+                        .fold(glm::Vec2::zeros(), |sum, value| sum + value)
+                        / inverse_w
+                };
+                let uv = interpolate(|vertex| vertex.uv);
+                let light = interpolate(|vertex| vertex.light);
+                let ambient_occlusion: f32 = (0..3)
+                    .map(|i| triangle[i].ambient_occlusion * (weights[i] * screen[i].w))
+                    .sum::<f32>()
+                    / inverse_w;
+
+                let mut texel = self.sample(layer, &uv);
+                if texel[3] == 0 {
+                    continue;
+                }
+                let shade = brightness(light.x).max(brightness(light.y))
+                    * ambient_occlusion_brightness(ambient_occlusion);
+                for channel in texel.0.iter_mut().take(3) {
+                    *channel = (*channel as f32 * shade).round() as u8;
+                }
+                if blend {
+                    let alpha = texel[3] as f32 / 255.0;
+                    let pixel = self.color.get_pixel_mut(x, y);
+                    for channel in 0..3 {
+                        let blended =
+                            texel[channel] as f32 * alpha + pixel[channel] as f32 * (1.0 - alpha);
+                        pixel[channel] = blended.round() as u8;
+                    }
+                } else {
+                    self.depth[index] = depth;
+                    self.color.put_pixel(x, y, texel);
+                }
+            }
+        }
+    }
+
+    /// Samples the block textures with block.fs.glsl's flipped v coordinate
+    fn sample(&self, layer: i32, uv: &glm::Vec2) -> Rgba<u8> {
+        let layer = self
+            .animated_tiles
+            .iter()
+            .find(|(tile, _)| *tile == layer)
+            .map_or(layer, |(_, frame)| *frame);
+        sample_layer(&self.tiles, layer, uv.x, -uv.y)
+    }
+}
+
+/// Nearest neighbor lookup in one layer of a texture array, with repeating wrap.
+/// Layers outside the array are transparent.
+fn sample_layer(layers: &[RgbaImage], layer: i32, u: f32, v: f32) -> Rgba<u8> {
+    let image = match layers.get(layer as usize) {
+        Some(image) if layer >= 0 => image,
+        _ => return Rgba([0, 0, 0, 0]),
+    };
+    let (u, v) = (u - u.floor(), v - v.floor());
+    let x = ((u * image.width() as f32) as u32).min(image.width() - 1);
+    let y = ((v * image.height() as f32) as u32).min(image.height() - 1);
+    *image.get_pixel(x, y)
+}
+
+fn edge(a: &glm::Vec4, b: &glm::Vec4, point: &glm::Vec4) -> f32 {
+    (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x)
+}
+
+/// Clips a polygon against the near plane (z = 0 in clip space for a zero-to-one depth range)
+fn clip_near(polygon: &[ClipVertex]) -> Vec<ClipVertex> {
+    let distance = |vertex: &ClipVertex| vertex.position.z;
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (index, current) in polygon.iter().enumerate() {
+        let next = &polygon[(index + 1) % polygon.len()];
+        let (current_distance, next_distance) = (distance(current), distance(next));
+        if current_distance >= 0.0 {
+            clipped.push(*current);
+        }
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let t = current_distance / (current_distance - next_distance);
+            clipped.push(current.lerp(next, t));
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block::Block, terrain::FlatTerrainGenerator, world::test_world};
+
+    const SIZE: u32 = 32;
+
+    /// One flat colored layer per tile, so each pixel shows which tile it came from
+    fn tile_colors() -> Vec<RgbaImage> {
+        (0..=255)
+            .map(|layer| {
+                RgbaImage::from_pixel(4, 4, Rgba([layer as u8, 255 - layer as u8, 128, 255]))
+            })
+            .collect()
+    }
+
+    fn renderer() -> SoftwareRenderer {
+        let mut renderer = SoftwareRenderer::new(SIZE, SIZE, tile_colors(), FONT_PATH).unwrap();
+        renderer.clear();
+        renderer
+    }
+
+    fn view_projection(eye: &glm::Vec3, target: &glm::Vec3, up: &glm::Vec3) -> glm::Mat4 {
+        glm::perspective_zo(1.0, 60f32.to_radians(), 0.1, 100.0) * glm::look_at(eye, target, up)
+    }
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex {
+            position: [x, y, z],
+            uv: [0.0, 0.0],
+            layer: 3,
+            light: [1.0, 0.0],
+            ambient_occlusion: 1.0,
+        }
+    }
+
+    #[test]
+    fn renders_a_block_from_above() {
+        let mut world = test_world(FlatTerrainGenerator { height: 0 });
+        world.view_distance = 0;
+        world.update(&glm::vec3(8.0, 20.0, 8.0));
+        world.finish_loading();
+        assert!(world.set_block(&glm::vec3(8, 10, 8), Block::Cobblestone));
+        let top = world.registry.configuration(Block::Cobblestone).top;
+
+        let eye = glm::vec3(8.5, 14.0, 8.5);
+        let view_projection =
+            view_projection(&eye, &glm::vec3(8.5, 0.0, 8.5), &glm::vec3(0.0, 0.0, -1.0));
+        let mut renderer = renderer();
+        let stats = renderer.render_world(&world, &view_projection, &eye, &mut Profiler::new());
+        assert_eq!(stats.drawn, 1);
+
+        // The top face is fully lit and unoccluded, so it shows its tile unshaded
+        let center = SIZE / 2;
+        assert_eq!(
+            *renderer.color.get_pixel(center, center),
+            Rgba([top as u8, 255 - top as u8, 128, 255])
+        );
+        let face = view_projection * glm::vec4(8.5, 11.0, 8.5, 1.0);
+        let depth = renderer.depth[(center * SIZE + center) as usize];
+        assert!((depth - face.z / face.w).abs() < 1e-4, "depth {}", depth);
+
+        // The block covers about a third of the view across, leaving the corners empty
+        assert_eq!(*renderer.color.get_pixel(0, 0), renderer.background);
+        assert_eq!(renderer.depth[0], f32::INFINITY);
+        let covered = renderer
+            .depth
+            .iter()
+            .filter(|depth| depth.is_finite())
+            .count();
+        assert!(covered > 50 && covered < 200, "{} pixels covered", covered);
+    }
+
+    #[test]
+    fn clips_triangles_crossing_the_near_plane() {
+        // A floor triangle reaching from behind the camera to far ahead of it
+        let eye = glm::vec3(0.0, 1.0, 0.0);
+        let view_projection =
+            view_projection(&eye, &glm::vec3(0.0, 1.0, -1.0), &glm::vec3(0.0, 1.0, 0.0));
+        let triangle = [
+            vertex(-4.0, 0.0, 4.0),
+            vertex(4.0, 0.0, 4.0),
+            vertex(0.0, 0.0, -50.0),
+        ];
+        let clip = triangle
+            .iter()
+            .map(|vertex| ClipVertex {
+                position: view_projection
+                    * glm::vec4(
+                        vertex.position[0],
+                        vertex.position[1],
+                        vertex.position[2],
+                        1.0,
+                    ),
+                uv: glm::Vec2::zeros(),
+                light: glm::vec2(1.0, 0.0),
+                ambient_occlusion: 1.0,
+            })
+            .collect::<Vec<_>>();
+        assert!(clip[0].position.z < 0.0 && clip[1].position.z < 0.0);
+        let clipped = clip_near(&clip);
+        assert_eq!(clipped.len(), 3);
+        assert!(clipped.iter().all(|vertex| vertex.position.z >= -1e-6));
+
+        let mut renderer = renderer();
+        renderer.draw_triangle(&view_projection, &triangle, false);
+        // The floor is only below the horizon. Unclipped, the vertices behind
+        // the camera would project above it and smear across the top rows.
+        for y in 0..SIZE / 2 {
+            for x in 0..SIZE {
+                assert_eq!(
+                    *renderer.color.get_pixel(x, y),
+                    renderer.background,
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+        let bottom_row = (0..SIZE).map(|x| *renderer.color.get_pixel(x, SIZE - 1));
+        assert!(bottom_row
+            .into_iter()
+            .all(|pixel| pixel == Rgba([3, 252, 128, 255])));
+    }
+
+    #[test]
+    fn hud_quads_blend_over_the_frame() {
+        let mut renderer = renderer();
+        renderer.draw_hud(&[HudQuad {
+            min: glm::vec2(-4.0, 8.0),
+            max: glm::vec2(8.0, 40.0),
+            color: glm::vec4(1.0, 0.0, 0.0, 0.5),
+            texture: QuadTexture::None,
+        }]);
+        assert_eq!(*renderer.color.get_pixel(0, 8), Rgba([160, 32, 32, 255]));
+        assert_eq!(
+            *renderer.color.get_pixel(7, SIZE - 1),
+            Rgba([160, 32, 32, 255])
+        );
+        assert_eq!(*renderer.color.get_pixel(8, 8), renderer.background);
+        assert_eq!(*renderer.color.get_pixel(0, 7), renderer.background);
+    }
+
+    #[test]
+    fn samples_wrap_and_missing_layers_are_transparent() {
+        let layers = tile_colors();
+        assert_eq!(
+            sample_layer(&layers, 5, 1.25, -0.5),
+            Rgba([5, 250, 128, 255])
+        );
+        assert_eq!(sample_layer(&layers, -1, 0.5, 0.5), Rgba([0, 0, 0, 0]));
+        assert_eq!(sample_layer(&layers, 256, 0.5, 0.5), Rgba([0, 0, 0, 0]));
+    }
+}
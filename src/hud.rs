@@ -0,0 +1,500 @@
+use anyhow::{Context, Result};
+use gl::types::*;
+use nalgebra_glm as glm;
+
+use crate::{
+    block::create_texture_array,
+    inventory::{GameMode, Inventory, HOTBAR_SLOTS},
+    profiler::Profiler,
+    registry::BlockRegistry,
+    shader::{ShaderError, ShaderProgram},
+    texture_pack::split_tiles,
+};
+
+pub const FONT_PATH: &str = "assets/textures/font.png";
+/// The width and height of a glyph in font.png, which holds the printable ASCII characters
+/// in rows of 16, starting from the space
+pub const GLYPH_SIZE: u32 = 8;
+const FIRST_GLYPH: u8 = b' ';
+/// Horizontal distance between the starts of neighboring characters
+const GLYPH_ADVANCE: f32 = 6.0;
+const LINE_HEIGHT: f32 = 10.0;
+
+/// The window height the HUD is laid out for at a scale of 1.
+/// Taller windows scale it up by whole numbers to keep the pixels crisp.
+const BASE_HEIGHT: u32 = 240;
+const MARGIN: f32 = 2.0;
+const CROSSHAIR_SIZE: f32 = 9.0;
+const SLOT_SIZE: f32 = 20.0;
+const ICON_SIZE: f32 = 16.0;
+/// Thickness of the frame around the selected slot
+const SELECTION_WIDTH: f32 = 1.0;
+
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SHADOW: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const CROSSHAIR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+
+/// What fills a quad, multiplied by its color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuadTexture {
+    None,
+    /// A tile of the block atlas
+    Tile(i32),
+    /// A character of font.png, counted from the space
+    Glyph(u32),
+}
+
+/// A rectangle on the screen in pixels, measured from the top left corner of the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HudQuad {
+    pub min: glm::Vec2,
+    pub max: glm::Vec2,
+    pub texture: QuadTexture,
+    pub color: glm::Vec4,
+}
+
+impl HudQuad {
+    fn new(min: glm::Vec2, size: glm::Vec2, texture: QuadTexture, color: [f32; 4]) -> Self {
+        Self {
+            min,
+            max: min + size,
+            texture,
+            color: glm::Vec4::from(color),
+        }
+    }
+}
+
+/// How many screen pixels each HUD pixel covers
+pub fn hud_scale(window_dimensions: [u32; 2]) -> f32 {
+    (window_dimensions[1] / BASE_HEIGHT).max(1) as f32
+}
+
+/// Lays out the crosshair, the hotbar and lines of text in the top left corner,
+/// in the order they should be drawn
+pub fn layout_hud(
+    window_dimensions: [u32; 2],
+    inventory: &Inventory,
+    registry: &BlockRegistry,
+    text_lines: &[String],
+) -> Vec<HudQuad> {
+    let scale = hud_scale(window_dimensions);
+    let window = glm::vec2(window_dimensions[0] as f32, window_dimensions[1] as f32);
+    let mut quads = Vec::new();
+    layout_crosshair(&window, scale, &mut quads);
+    layout_hotbar(&window, scale, inventory, registry, &mut quads);
+
+    for (index, line) in text_lines.iter().enumerate() {
+        let position = glm::vec2(MARGIN, MARGIN + index as f32 * LINE_HEIGHT) * scale;
+        let padding = glm::vec2(1.0, 1.0) * scale;
+        quads.push(HudQuad::new(
+            position - padding,
+            glm::vec2(text_width(line), LINE_HEIGHT) * scale + padding,
+            QuadTexture::None,
+            BACKDROP,
+        ));
+        layout_text(line, &position, scale, &mut quads);
+    }
+    quads
+}
+
+fn layout_crosshair(window: &glm::Vec2, scale: f32, quads: &mut Vec<HudQuad>) {
+    let center = (window / 2.0).map(f32::floor);
+    let length = CROSSHAIR_SIZE * scale;
+    let half = (CROSSHAIR_SIZE / 2.0).floor() * scale;
+    quads.push(HudQuad::new(
+        center - glm::vec2(half, 0.0),
+        glm::vec2(length, scale),
+        QuadTexture::None,
+        CROSSHAIR,
+    ));
+    // The vertical bar leaves out the middle so it doesn't cover it twice
+    quads.push(HudQuad::new(
+        center - glm::vec2(0.0, half),
+        glm::vec2(scale, half),
+        QuadTexture::None,
+        CROSSHAIR,
+    ));
+    quads.push(HudQuad::new(
+        center + glm::vec2(0.0, scale),
+        glm::vec2(scale, length - half - scale),
+        QuadTexture::None,
+        CROSSHAIR,
+    ));
+}
+
+fn layout_hotbar(
+    window: &glm::Vec2,
+    scale: f32,
+    inventory: &Inventory,
+    registry: &BlockRegistry,
+    quads: &mut Vec<HudQuad>,
+) {
+    let slot_size = SLOT_SIZE * scale;
+    let width = slot_size * HOTBAR_SLOTS as f32;
+    let origin = glm::vec2(
+        ((window.x - width) / 2.0).floor(),
+        window.y - slot_size - MARGIN * scale,
+    );
+    quads.push(HudQuad::new(
+        origin,
+        glm::vec2(width, slot_size),
+        QuadTexture::None,
+        BACKDROP,
+    ));
+
+    for (slot, stack) in inventory.slots().iter().enumerate() {
+        let corner = origin + glm::vec2(slot as f32 * slot_size, 0.0);
+        if slot == inventory.selected() {
+            layout_frame(&corner, slot_size, SELECTION_WIDTH * scale, quads);
+        }
+        let stack = match stack {
+            Some(stack) => stack,
+            None => continue,
+        };
+        let inset = (SLOT_SIZE - ICON_SIZE) / 2.0 * scale;
+        let tile = registry.configuration(stack.block).front;
+        quads.push(HudQuad::new(
+            corner + glm::vec2(inset, inset),
+            glm::vec2(ICON_SIZE, ICON_SIZE) * scale,
+            QuadTexture::Tile(tile),
+            WHITE,
+        ));
+        if inventory.game_mode == GameMode::Survival {
+            let count = stack.count.to_string();
+            let position = corner + glm::vec2(SLOT_SIZE - 1.0 - text_width(&count), 12.0) * scale;
+            layout_text(&count, &position, scale, quads);
+        }
+    }
+}
+
+/// Four bars around the inside of a square
+fn layout_frame(corner: &glm::Vec2, size: f32, thickness: f32, quads: &mut Vec<HudQuad>) {
+    let bars = [
+        (glm::vec2(0.0, 0.0), glm::vec2(size, thickness)),
+        (glm::vec2(0.0, size - thickness), glm::vec2(size, thickness)),
+        (
+            glm::vec2(0.0, thickness),
+            glm::vec2(thickness, size - 2.0 * thickness),
+        ),
+        (
+            glm::vec2(size - thickness, thickness),
+            glm::vec2(thickness, size - 2.0 * thickness),
+        ),
+    ];
+    for (offset, extent) in bars.iter() {
+        quads.push(HudQuad::new(
+            corner + offset,
+            *extent,
+            QuadTexture::None,
+            WHITE,
+        ));
+    }
+}
+
+/// The width of a line of text at a scale of 1
+pub fn text_width(text: &str) -> f32 {
+    text.chars().count() as f32 * GLYPH_ADVANCE
+}
+
+/// Lays out white text with a drop shadow, one glyph per character.
+/// Characters missing from the font are shown as question marks.
+fn layout_text(text: &str, position: &glm::Vec2, scale: f32, quads: &mut Vec<HudQuad>) {
+    let size = glm::vec2(GLYPH_SIZE as f32, GLYPH_SIZE as f32) * scale;
+    for (color, offset) in [(SHADOW, scale), (WHITE, 0.0)].iter() {
+        for (index, character) in text.chars().enumerate() {
+            if character == ' ' {
+                continue;
+            }
+            let glyph = if character.is_ascii_graphic() {
+                character as u8
+            } else {
+                b'?'
+            };
+            let corner =
+                position + glm::vec2(index as f32 * GLYPH_ADVANCE * scale + offset, *offset);
+            quads.push(HudQuad::new(
+                corner,
+                size,
+                QuadTexture::Glyph((glyph - FIRST_GLYPH) as u32),
+                *color,
+            ));
+        }
+    }
+}
+
+/// Where a vertex's color comes from, matching hud.fs.glsl
+const SOURCE_COLOR: i32 = 0;
+const SOURCE_ATLAS: i32 = 1;
+const SOURCE_FONT: i32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct HudVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+    layer: i32,
+    source: i32,
+}
+
+fn hud_vertices(quads: &[HudQuad]) -> Vec<HudVertex> {
+    let mut vertices = Vec::with_capacity(quads.len() * 6);
+    for quad in quads.iter() {
+        let (layer, source) = match quad.texture {
+            QuadTexture::None => (0, SOURCE_COLOR),
+            QuadTexture::Tile(tile) => (tile, SOURCE_ATLAS),
+            QuadTexture::Glyph(glyph) => (glyph as i32, SOURCE_FONT),
+        };
+        let corner = |u: f32, v: f32| HudVertex {
+            position: [
+                quad.min.x + (quad.max.x - quad.min.x) * u,
+                quad.min.y + (quad.max.y - quad.min.y) * v,
+            ],
+            uv: [u, v],
+            color: [quad.color.x, quad.color.y, quad.color.z, quad.color.w],
+            layer,
+            source,
+        };
+        let (top_left, top_right) = (corner(0.0, 0.0), corner(1.0, 0.0));
+        let (bottom_left, bottom_right) = (corner(0.0, 1.0), corner(1.0, 1.0));
+        vertices.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            bottom_right,
+            top_left,
+            bottom_right,
+            top_right,
+        ]);
+    }
+    vertices
+}
+
+/// Draws laid out HUD quads over the frame with an orthographic projection
+pub struct HudRenderer {
+    shader: ShaderProgram,
+    font: GLuint,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl HudRenderer {
+    pub fn new() -> Result<Self> {
+        let shader =
+            ShaderProgram::new("assets/shaders/hud.vs.glsl", "assets/shaders/hud.fs.glsl")?;
+        let font = image::open(FONT_PATH)
+            .with_context(|| format!("Failed to open font '{}'", FONT_PATH))?
+            .to_rgba8();
+        let font = create_texture_array(&split_tiles(&font, GLYPH_SIZE))?;
+        let (mut vao, mut vbo) = (0, 0);
+        let stride = std::mem::size_of::<HudVertex>() as GLsizei;
+        let float_size = std::mem::size_of::<GLfloat>();
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (2 * float_size) as *const GLvoid,
+            );
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribPointer(
+                2,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (4 * float_size) as *const GLvoid,
+            );
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribIPointer(3, 1, gl::INT, stride, (8 * float_size) as *const GLvoid);
+            gl::EnableVertexAttribArray(4);
+            gl::VertexAttribIPointer(4, 1, gl::INT, stride, (9 * float_size) as *const GLvoid);
+
+            gl::BindVertexArray(0);
+        }
+        Ok(Self {
+            shader,
+            font,
+            vao,
+            vbo,
+        })
+    }
+
+    /// Rebuilds the HUD shaders if their files changed, returning whether they were
+    pub fn reload_shaders(&mut self) -> Result<bool, ShaderError> {
+        self.shader.reload_if_changed()
+    }
+
+    /// Draws the quads in order on top of everything, sampling block icons from the atlas
+    pub unsafe fn draw(
+        &self,
+        quads: &[HudQuad],
+        atlas: GLuint,
+        window_dimensions: [u32; 2],
+        profiler: &mut Profiler,
+    ) -> Result<()> {
+        let vertices = hud_vertices(quads);
+        if vertices.is_empty() {
+            return Ok(());
+        }
+
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        gl::UseProgram(self.shader.id());
+        let projection = glm::ortho(
+            0.0,
+            window_dimensions[0] as f32,
+            window_dimensions[1] as f32,
+            0.0,
+            -1.0,
+            1.0,
+        );
+        let projection_location = self.shader.uniform_location("projection")?;
+        gl::UniformMatrix4fv(projection_location, 1, gl::FALSE, projection.as_ptr());
+        gl::Uniform1i(self.shader.uniform_location("atlas")?, 0);
+        gl::Uniform1i(self.shader.uniform_location("font")?, 1);
+
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, atlas);
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.font);
+
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            (vertices.len() * std::mem::size_of::<HudVertex>()) as GLsizeiptr,
+            vertices.as_ptr() as *const GLvoid,
+            gl::STREAM_DRAW,
+        );
+        gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as _);
+        profiler.record_draw(vertices.len() / 3);
+        gl::BindVertexArray(0);
+
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        Ok(())
+    }
+}
+
+impl Drop for HudRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.font);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::Block, inventory::MAX_STACK_SIZE, terrain::FlatTerrainGenerator, world::test_world,
+    };
+
+    const CROSSHAIR_QUADS: usize = 3;
+    const WINDOW_SIZES: [[u32; 2]; 2] = [[640, 480], [1920, 1080]];
+
+    /// The smallest rectangle around a set of quads
+    fn bounds(quads: &[HudQuad]) -> (glm::Vec2, glm::Vec2) {
+        quads
+            .iter()
+            .fold((quads[0].min, quads[0].max), |(min, max), quad| {
+                (glm::min2(&min, &quad.min), glm::max2(&max, &quad.max))
+            })
+    }
+
+    fn full_inventory() -> Inventory {
+        let blocks = [Block::Cobblestone; HOTBAR_SLOTS];
+        let mut inventory = Inventory::with_blocks(GameMode::Survival, &blocks);
+        inventory.select(HOTBAR_SLOTS - 1);
+        assert_eq!(inventory.slots()[0].unwrap().count, MAX_STACK_SIZE);
+        inventory
+    }
+
+    #[test]
+    fn scales_by_whole_numbers() {
+        assert_eq!(hud_scale([640, 200]), 1.0);
+        assert_eq!(hud_scale([640, 480]), 2.0);
+        assert_eq!(hud_scale([1920, 1080]), 4.0);
+    }
+
+    #[test]
+    fn crosshair_is_centered() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        for dimensions in WINDOW_SIZES.iter() {
+            let quads = layout_hud(*dimensions, &full_inventory(), &world.registry, &[]);
+            let (min, max) = bounds(&quads[..CROSSHAIR_QUADS]);
+            let scale = hud_scale(*dimensions);
+            let window = glm::vec2(dimensions[0] as f32, dimensions[1] as f32);
+            assert_eq!(max - min, glm::vec2(CROSSHAIR_SIZE, CROSSHAIR_SIZE) * scale);
+            // Centered on the pixel at the middle of the window
+            let middle = (min + max) / 2.0;
+            assert!(
+                (middle - window / 2.0).abs().max() <= scale / 2.0,
+                "{:?} in {:?}",
+                middle,
+                dimensions
+            );
+        }
+    }
+
+    #[test]
+    fn hotbar_is_on_screen() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        for dimensions in WINDOW_SIZES.iter() {
+            let quads = layout_hud(*dimensions, &full_inventory(), &world.registry, &[]);
+            let hotbar = &quads[CROSSHAIR_QUADS..];
+            let (min, max) = bounds(hotbar);
+            let window = glm::vec2(dimensions[0] as f32, dimensions[1] as f32);
+            assert!(
+                min.x >= 0.0 && min.y >= 0.0,
+                "{:?} in {:?}",
+                min,
+                dimensions
+            );
+            assert!(
+                max.x <= window.x && max.y <= window.y,
+                "{:?} in {:?}",
+                max,
+                dimensions
+            );
+            let backdrop = hotbar[0];
+            assert!(((backdrop.min.x + backdrop.max.x) / 2.0 - window.x / 2.0).abs() <= 1.0);
+            assert!(max.y > window.y * 0.75, "the hotbar is along the bottom");
+
+            let icons = hotbar
+                .iter()
+                .filter(|quad| matches!(quad.texture, QuadTexture::Tile(_)))
+                .count();
+            assert_eq!(icons, HOTBAR_SLOTS);
+        }
+    }
+
+    #[test]
+    fn text_starts_in_the_top_left_corner() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let lines = ["x: 1".to_string(), "y: 2".to_string()];
+        let inventory = Inventory::default();
+        let without_text = layout_hud([640, 480], &inventory, &world.registry, &[]);
+        let quads = layout_hud([640, 480], &inventory, &world.registry, &lines);
+        let (min, max) = bounds(&quads[without_text.len()..]);
+        assert!(min.x >= 0.0 && min.y >= 0.0);
+        assert!(max.x < 320.0 && max.y < 240.0);
+    }
+}
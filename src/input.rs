@@ -5,24 +5,20 @@ use glutin::{
     event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
 };
 use nalgebra_glm as glm;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::gamepad::GamepadState;
 
 pub type KeyMap = HashMap<VirtualKeyCode, ElementState>;
 
+#[derive(Default)]
 pub struct Input {
     pub keystates: KeyMap,
+    /// Keys that went down since the last batch of events, ignoring key repeats
+    pub just_pressed: HashSet<VirtualKeyCode>,
+    pub just_released: HashSet<VirtualKeyCode>,
     pub mouse: Mouse,
-    pub allowed: bool,
-}
-
-impl Default for Input {
-    fn default() -> Self {
-        Self {
-            keystates: KeyMap::default(),
-            mouse: Mouse::default(),
-            allowed: true,
-        }
-    }
+    pub gamepad: GamepadState,
 }
 
 impl Input {
@@ -30,20 +26,45 @@ impl Input {
         self.keystates.contains_key(&keycode) && self.keystates[&keycode] == ElementState::Pressed
     }
 
+    pub fn is_key_just_pressed(&self, keycode: VirtualKeyCode) -> bool {
+        self.just_pressed.contains(&keycode)
+    }
+
+    pub fn is_key_just_released(&self, keycode: VirtualKeyCode) -> bool {
+        self.just_released.contains(&keycode)
+    }
+
     pub fn handle_event<T>(&mut self, event: &Event<T>, window_center: glm::Vec2) {
-        if let Event::WindowEvent { event, .. } = event {
-            if let WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        virtual_keycode: Some(keycode),
-                        state,
-                        ..
-                    },
-                ..
-            } = *event
-            {
-                *self.keystates.entry(keycode).or_insert(state) = state;
+        if let Event::NewEvents(..) = event {
+            self.just_pressed.clear();
+            self.just_released.clear();
+        }
+
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            virtual_keycode: Some(keycode),
+                            state,
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } = event
+        {
+            let was_pressed = self.is_key_pressed(*keycode);
+            match state {
+                ElementState::Pressed if !was_pressed => {
+                    self.just_pressed.insert(*keycode);
+                }
+                ElementState::Released if was_pressed => {
+                    self.just_released.insert(*keycode);
+                }
+                _ => {}
             }
+            *self.keystates.entry(*keycode).or_insert(*state) = *state;
         }
 
         self.mouse.handle_event(event, window_center);
@@ -52,17 +73,32 @@ impl Input {
 
 #[derive(Default)]
 pub struct Mouse {
-    pub is_left_clicked: bool,
-    pub is_right_clicked: bool,
+    pub pressed_buttons: HashSet<MouseButton>,
+    /// Buttons that went down since the last batch of events
+    pub just_pressed_buttons: HashSet<MouseButton>,
+    pub just_released_buttons: HashSet<MouseButton>,
     pub position: glm::Vec2,
     pub position_delta: glm::Vec2,
     pub offset_from_center: glm::Vec2,
+    /// Lines scrolled since the last batch of events
     pub wheel_delta: glm::Vec2,
     pub moved: bool,
     pub scrolled: bool,
 }
 
 impl Mouse {
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn is_button_just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed_buttons.contains(&button)
+    }
+
+    pub fn is_button_just_released(&self, button: MouseButton) -> bool {
+        self.just_released_buttons.contains(&button)
+    }
+
     pub fn handle_event<T>(&mut self, event: &Event<T>, window_center: glm::Vec2) {
         match event {
             Event::NewEvents { .. } => self.new_events(),
@@ -82,9 +118,10 @@ impl Mouse {
     }
 
     fn new_events(&mut self) {
-        if !self.scrolled {
-            self.wheel_delta = glm::vec2(0.0, 0.0);
-        }
+        self.just_pressed_buttons.clear();
+        self.just_released_buttons.clear();
+
+        self.wheel_delta = glm::vec2(0.0, 0.0);
         self.scrolled = false;
 
         if !self.moved {
@@ -103,16 +140,89 @@ impl Mouse {
     }
 
     fn mouse_wheel(&mut self, h_lines: f32, v_lines: f32) {
-        self.wheel_delta = glm::vec2(h_lines, v_lines);
+        self.wheel_delta += glm::vec2(h_lines, v_lines);
         self.scrolled = true;
     }
 
     fn mouse_input(&mut self, button: MouseButton, state: ElementState) {
-        let clicked = state == ElementState::Pressed;
-        match button {
-            MouseButton::Left => self.is_left_clicked = clicked,
-            MouseButton::Right => self.is_right_clicked = clicked,
-            _ => {}
+        match state {
+            ElementState::Pressed => {
+                if self.pressed_buttons.insert(button) {
+                    self.just_pressed_buttons.insert(button);
+                }
+            }
+            ElementState::Released => {
+                if self.pressed_buttons.remove(&button) {
+                    self.just_released_buttons.insert(button);
+                }
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::InputEvent;
+    use glutin::event::StartCause;
+
+    fn key(input: &mut Input, key: VirtualKeyCode, pressed: bool) {
+        let event = InputEvent::Key { key, pressed }.to_event();
+        input.handle_event(&event, glm::Vec2::zeros());
+    }
+
+    fn mouse_button(input: &mut Input, button: MouseButton, pressed: bool) {
+        let event = InputEvent::MouseButton { button, pressed }.to_event();
+        input.handle_event(&event, glm::Vec2::zeros());
+    }
+
+    fn new_events(input: &mut Input) {
+        input.handle_event(
+            &Event::<()>::NewEvents(StartCause::Poll),
+            glm::Vec2::zeros(),
+        );
+    }
+
+    #[test]
+    fn key_repeats_are_not_new_presses() {
+        let mut input = Input::default();
+        new_events(&mut input);
+        key(&mut input, VirtualKeyCode::W, true);
+        assert!(input.is_key_just_pressed(VirtualKeyCode::W));
+
+        new_events(&mut input);
+        key(&mut input, VirtualKeyCode::W, true);
+        assert!(input.is_key_pressed(VirtualKeyCode::W));
+        assert!(!input.is_key_just_pressed(VirtualKeyCode::W));
+    }
+
+    #[test]
+    fn edges_clear_on_new_events() {
+        let mut input = Input::default();
+        new_events(&mut input);
+        key(&mut input, VirtualKeyCode::W, true);
+        mouse_button(&mut input, MouseButton::Left, true);
+        new_events(&mut input);
+        assert!(!input.is_key_just_pressed(VirtualKeyCode::W));
+        assert!(!input.mouse.is_button_just_pressed(MouseButton::Left));
+
+        key(&mut input, VirtualKeyCode::W, false);
+        mouse_button(&mut input, MouseButton::Left, false);
+        assert!(input.is_key_just_released(VirtualKeyCode::W));
+        assert!(input.mouse.is_button_just_released(MouseButton::Left));
+        new_events(&mut input);
+        assert!(!input.is_key_just_released(VirtualKeyCode::W));
+        assert!(!input.mouse.is_button_just_released(MouseButton::Left));
+        assert!(!input.is_key_pressed(VirtualKeyCode::W));
+        assert!(!input.mouse.is_button_pressed(MouseButton::Left));
+    }
+
+    #[test]
+    fn releasing_an_unpressed_key_is_not_an_edge() {
+        let mut input = Input::default();
+        key(&mut input, VirtualKeyCode::W, false);
+        mouse_button(&mut input, MouseButton::Left, false);
+        assert!(!input.is_key_just_released(VirtualKeyCode::W));
+        assert!(!input.mouse.is_button_just_released(MouseButton::Left));
+    }
+}
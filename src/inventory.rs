@@ -0,0 +1,195 @@
+use crate::block::Block;
+
+pub const HOTBAR_SLOTS: usize = 9;
+pub const MAX_STACK_SIZE: u32 = 64;
+
+/// Whether blocks are used up when placed and collected when broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Blocks are never used up
+    Creative,
+    Survival,
+}
+
+/// A number of the same block held in one slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemStack {
+    pub block: Block,
+    /// From 1 to `MAX_STACK_SIZE`
+    pub count: u32,
+}
+
+/// The blocks the player carries, all of which are on the hotbar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inventory {
+    pub game_mode: GameMode,
+    slots: [Option<ItemStack>; HOTBAR_SLOTS],
+    selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new(GameMode::Creative)
+    }
+}
+
+impl Inventory {
+    pub fn new(game_mode: GameMode) -> Self {
+        Self {
+            game_mode,
+            slots: [None; HOTBAR_SLOTS],
+            selected: 0,
+        }
+    }
+
+    /// Creates an inventory with a full stack of each block in the first slots
+    pub fn with_blocks(game_mode: GameMode, blocks: &[Block]) -> Self {
+        let mut inventory = Self::new(game_mode);
+        for (slot, block) in inventory.slots.iter_mut().zip(blocks.iter()) {
+            *slot = Some(ItemStack {
+                block: *block,
+                count: MAX_STACK_SIZE,
+            });
+        }
+        inventory
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>; HOTBAR_SLOTS] {
+        &self.slots
+    }
+
+    /// Replaces a slot's contents, ignoring slots past the end of the hotbar.
+    /// Empty stacks leave the slot empty and counts are capped at `MAX_STACK_SIZE`.
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        if let Some(destination) = self.slots.get_mut(slot) {
+            *destination = stack
+                .filter(|stack| stack.count > 0)
+                .map(|stack| ItemStack {
+                    count: stack.count.min(MAX_STACK_SIZE),
+                    ..stack
+                });
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects a slot, ignoring slots past the end of the hotbar
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection by a number of slots, wrapping around at either end
+    pub fn scroll(&mut self, slots: i32) {
+        self.selected = (self.selected as i32 + slots).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+
+    /// Takes one of the selected block to place it.
+    /// In survival mode this uses up the block, emptying the slot after the last one.
+    pub fn take_selected(&mut self) -> Option<Block> {
+        let slot = &mut self.slots[self.selected];
+        let stack = slot.as_mut()?;
+        let block = stack.block;
+        if self.game_mode == GameMode::Survival {
+            stack.count -= 1;
+            if stack.count == 0 {
+                *slot = None;
+            }
+        }
+        Some(block)
+    }
+
+    /// Picks up a broken block in survival mode, topping up a stack of
+    /// the same block before starting a new one in the first empty slot.
+    /// Returns false if there was no room for it.
+    pub fn collect(&mut self, block: Block) -> bool {
+        if self.game_mode == GameMode::Creative {
+            return true;
+        }
+        let existing = self
+            .slots
+            .iter_mut()
+            .flatten()
+            .find(|stack| stack.block == block && stack.count < MAX_STACK_SIZE);
+        if let Some(stack) = existing {
+            stack.count += 1;
+            return true;
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(ItemStack { block, count: 1 });
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(block: Block, count: u32) -> Option<ItemStack> {
+        Some(ItemStack { block, count })
+    }
+
+    #[test]
+    fn survival_uses_up_placed_blocks() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        inventory.set_slot(0, stack(Block::Dirt, 2));
+        assert_eq!(inventory.take_selected(), Some(Block::Dirt));
+        assert_eq!(inventory.slots()[0], stack(Block::Dirt, 1));
+        assert_eq!(inventory.take_selected(), Some(Block::Dirt));
+        assert_eq!(inventory.slots()[0], None);
+        assert_eq!(inventory.take_selected(), None);
+    }
+
+    #[test]
+    fn creative_never_runs_out() {
+        let mut inventory = Inventory::with_blocks(GameMode::Creative, &[Block::Dirt]);
+        for _ in 0..MAX_STACK_SIZE + 1 {
+            assert_eq!(inventory.take_selected(), Some(Block::Dirt));
+        }
+        assert_eq!(inventory.slots()[0], stack(Block::Dirt, MAX_STACK_SIZE));
+        assert!(inventory.collect(Block::Cobblestone));
+        assert_eq!(inventory.slots()[1], None);
+    }
+
+    #[test]
+    fn collecting_tops_up_stacks_before_using_empty_slots() {
+        let mut inventory = Inventory::new(GameMode::Survival);
+        inventory.set_slot(2, stack(Block::Dirt, MAX_STACK_SIZE));
+        inventory.set_slot(4, stack(Block::Dirt, MAX_STACK_SIZE - 1));
+        assert!(inventory.collect(Block::Dirt));
+        assert_eq!(inventory.slots()[4], stack(Block::Dirt, MAX_STACK_SIZE));
+        assert!(inventory.collect(Block::Dirt));
+        assert_eq!(inventory.slots()[0], stack(Block::Dirt, 1));
+        assert!(inventory.collect(Block::Cobblestone));
+        assert_eq!(inventory.slots()[1], stack(Block::Cobblestone, 1));
+    }
+
+    #[test]
+    fn collecting_fails_when_full() {
+        let blocks = [Block::Dirt; HOTBAR_SLOTS];
+        let mut inventory = Inventory::with_blocks(GameMode::Survival, &blocks);
+        assert!(!inventory.collect(Block::Dirt));
+        assert!(!inventory.collect(Block::Cobblestone));
+        inventory.take_selected();
+        assert!(!inventory.collect(Block::Cobblestone));
+        assert!(inventory.collect(Block::Dirt));
+    }
+
+    #[test]
+    fn selection_wraps_around_the_hotbar() {
+        let mut inventory = Inventory::default();
+        inventory.scroll(-1);
+        assert_eq!(inventory.selected(), HOTBAR_SLOTS - 1);
+        inventory.scroll(2);
+        assert_eq!(inventory.selected(), 1);
+        inventory.select(HOTBAR_SLOTS);
+        assert_eq!(inventory.selected(), 1);
+    }
+}
@@ -0,0 +1,346 @@
+use nalgebra_glm as glm;
+use std::collections::VecDeque;
+
+use crate::{
+    block::Block,
+    registry::BlockRegistry,
+    world::{ChunkPosition, World, CHUNK_DEPTH, CHUNK_LENGTH, CHUNK_WIDTH},
+};
+
+pub const MAX_LIGHT: u8 = 15;
+/// How much dimmer each light level is than the one above it, matching block.fs.glsl
+const LIGHT_FALLOFF: f32 = 0.8;
+
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+/// The two independent kinds of light stored for every voxel.
+/// Sky light comes down from the top of the world and block light from emitting blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+    /// The light a neighbor receives from a voxel at the given level.
+    /// Full sky light travels straight down without fading.
+    fn spread(&self, level: u8, direction: &glm::IVec3) -> u8 {
+        if *self == LightChannel::Sky && level == MAX_LIGHT && direction.y == -1 {
+            MAX_LIGHT
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+/// The fraction of full brightness a face receives at a light level between 0 and 1
+pub fn brightness(level: f32) -> f32 {
+    LIGHT_FALLOFF.powf(MAX_LIGHT as f32 * (1.0 - level))
+}
+
+fn is_opaque(registry: &BlockRegistry, block: Block) -> bool {
+    registry.configuration(block).is_opaque()
+}
+
+fn blocks_light(world: &World, registry: &BlockRegistry, position: &glm::IVec3) -> bool {
+    match world.block_at(position) {
+        Some(block) => is_opaque(registry, block),
+        None => true,
+    }
+}
+
+/// Computes the light of a chunk that was just added to the world.
+/// Sky light falls down each column until it reaches an opaque block,
+/// then both channels flood through the chunk and across its borders,
+/// including light coming in from neighbors that were loaded earlier.
+pub fn light_chunk(world: &mut World, position: &ChunkPosition) {
+    let registry = world.registry.clone();
+    let chunk = match world.chunks.get_mut(position) {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let origin = chunk.origin();
+
+    // The lowest y of each column that sky light reaches at full strength
+    let mut sky_floor = [[0; CHUNK_LENGTH]; CHUNK_WIDTH];
+    let mut block_sources = Vec::new();
+    for (x, floors) in sky_floor.iter_mut().enumerate() {
+        for (z, floor) in floors.iter_mut().enumerate() {
+            let mut open_to_sky = true;
+            *floor = CHUNK_DEPTH;
+            for y in (0..CHUNK_DEPTH).rev() {
+                let block = chunk.blocks[x][z][y];
+                let configuration = registry.configuration(block);
+                if open_to_sky && is_opaque(&registry, block) {
+                    open_to_sky = false;
+                }
+                if open_to_sky {
+                    *floor = y;
+                }
+                let local = glm::vec3(x as i32, y as i32, z as i32);
+                let sky = if open_to_sky { MAX_LIGHT } else { 0 };
+                chunk.set_light(&local, LightChannel::Sky, sky);
+                chunk.set_light(&local, LightChannel::Block, configuration.light_emission);
+                if configuration.light_emission > 0 {
+                    block_sources.push(origin + local);
+                }
+            }
+        }
+    }
+
+    // Full sky light only needs to spread sideways where a neighboring column is darker
+    let mut sky_sources = Vec::new();
+    for x in 0..CHUNK_WIDTH {
+        for z in 0..CHUNK_LENGTH {
+            let on_border = x == 0 || z == 0 || x == CHUNK_WIDTH - 1 || z == CHUNK_LENGTH - 1;
+            let deepest_neighbor = if on_border {
+                CHUNK_DEPTH
+            } else {
+                sky_floor[x - 1][z]
+                    .max(sky_floor[x + 1][z])
+                    .max(sky_floor[x][z - 1])
+                    .max(sky_floor[x][z + 1])
+            };
+            for y in sky_floor[x][z]..deepest_neighbor.min(CHUNK_DEPTH) {
+                sky_sources.push(origin + glm::vec3(x as i32, y as i32, z as i32));
+            }
+        }
+    }
+
+    // Light already in the neighboring chunks flows in across the border
+    let mut border_sources = Vec::new();
+    for x in -1..=CHUNK_WIDTH as i32 {
+        for z in -1..=CHUNK_LENGTH as i32 {
+            let outside_x = x == -1 || x == CHUNK_WIDTH as i32;
+            let outside_z = z == -1 || z == CHUNK_LENGTH as i32;
+            if outside_x == outside_z {
+                continue;
+            }
+            for y in 0..CHUNK_DEPTH as i32 {
+                border_sources.push(origin + glm::vec3(x, y, z));
+            }
+        }
+    }
+
+    let mut sky_queue = VecDeque::from(sky_sources);
+    let mut block_queue = VecDeque::from(block_sources);
+    for source in border_sources {
+        if world.light_at(&source, LightChannel::Sky).unwrap_or(0) > 1 {
+            sky_queue.push_back(source);
+        }
+        if world.light_at(&source, LightChannel::Block).unwrap_or(0) > 1 {
+            block_queue.push_back(source);
+        }
+    }
+    spread_light(world, &registry, LightChannel::Sky, sky_queue);
+    spread_light(world, &registry, LightChannel::Block, block_queue);
+}
+
+/// Updates the light around a block that was just replaced,
+/// darkening whatever the previous block lit or let through
+/// before flooding light back in from the surroundings.
+pub fn update_light(world: &mut World, position: &glm::IVec3, previous: Block) {
+    let registry = world.registry.clone();
+    let block = match world.block_at(position) {
+        Some(block) => block,
+        None => return,
+    };
+    let (old, new) = (
+        registry.configuration(previous),
+        registry.configuration(block),
+    );
+    if is_opaque(&registry, previous) == is_opaque(&registry, block)
+        && old.light_emission == new.light_emission
+    {
+        return;
+    }
+
+    for channel in LightChannel::ALL.iter() {
+        let mut queue = remove_light(world, &registry, *channel, position);
+
+        let level = match channel {
+            LightChannel::Sky
+                if position.y == CHUNK_DEPTH as i32 - 1 && !is_opaque(&registry, block) =>
+            {
+                MAX_LIGHT
+            }
+            LightChannel::Sky => 0,
+            LightChannel::Block => new.light_emission,
+        };
+        if level > 0 {
+            world.set_light(position, *channel, level);
+            queue.push_back(*position);
+        }
+
+        // An opened up block is lit by its neighbors
+        for direction in DIRECTIONS.iter() {
+            let neighbor = position + glm::IVec3::from(*direction);
+            if world.light_at(&neighbor, *channel).unwrap_or(0) > 1 {
+                queue.push_back(neighbor);
+            }
+        }
+        spread_light(world, &registry, *channel, queue);
+    }
+}
+
+/// Darkens everything lit by the light at a position.
+/// Returns the positions of brighter light that was reached on the way,
+/// which have to spread again to fill in the darkened area.
+fn remove_light(
+    world: &mut World,
+    registry: &BlockRegistry,
+    channel: LightChannel,
+    position: &glm::IVec3,
+) -> VecDeque<glm::IVec3> {
+    let mut relight = VecDeque::new();
+    let level = world.light_at(position, channel).unwrap_or(0);
+    if level == 0 {
+        return relight;
+    }
+    world.set_light(position, channel, 0);
+
+    let mut queue = VecDeque::new();
+    queue.push_back((*position, level));
+    while let Some((position, level)) = queue.pop_front() {
+        for direction in DIRECTIONS.iter() {
+            let direction = glm::IVec3::from(*direction);
+            let neighbor = position + direction;
+            let neighbor_level = match world.light_at(&neighbor, channel) {
+                Some(neighbor_level) if neighbor_level > 0 => neighbor_level,
+                _ => continue,
+            };
+            if neighbor_level < level || neighbor_level == channel.spread(level, &direction) {
+                // Emitters keep their own light and relight their surroundings
+                let emission = match (channel, world.block_at(&neighbor)) {
+                    (LightChannel::Block, Some(block)) => {
+                        registry.configuration(block).light_emission
+                    }
+                    _ => 0,
+                };
+                world.set_light(&neighbor, channel, emission);
+                if emission > 0 {
+                    relight.push_back(neighbor);
+                }
+                queue.push_back((neighbor, neighbor_level));
+            } else {
+                relight.push_back(neighbor);
+            }
+        }
+    }
+    relight
+}
+
+/// Breadth first flood fill outward from lit positions,
+/// raising each reachable non-opaque neighbor to the light it receives
+fn spread_light(
+    world: &mut World,
+    registry: &BlockRegistry,
+    channel: LightChannel,
+    mut queue: VecDeque<glm::IVec3>,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = world.light_at(&position, channel).unwrap_or(0);
+        if level <= 1 {
+            continue;
+        }
+        for direction in DIRECTIONS.iter() {
+            let direction = glm::IVec3::from(*direction);
+            let neighbor = position + direction;
+            let spread = channel.spread(level, &direction);
+            let current = match world.light_at(&neighbor, channel) {
+                Some(current) => current,
+                None => continue,
+            };
+            if current >= spread || blocks_light(world, registry, &neighbor) {
+                continue;
+            }
+            world.set_light(&neighbor, channel, spread);
+            queue.push_back(neighbor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{terrain::FlatTerrainGenerator, world::test_world};
+
+    /// Flat ground with its surface at y = height - 1, loaded around the origin chunk
+    fn flat_world(height: usize) -> World {
+        let mut world = test_world(FlatTerrainGenerator { height });
+        world.view_distance = 1;
+        world.update(&glm::vec3(8.0, 40.0, 8.0));
+        world.finish_loading();
+        world
+    }
+
+    fn block_light(world: &World, position: &glm::IVec3) -> u8 {
+        world.light_at(position, LightChannel::Block).unwrap()
+    }
+
+    fn sky_light(world: &World, position: &glm::IVec3) -> u8 {
+        world.light_at(position, LightChannel::Sky).unwrap()
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_borders() {
+        let mut world = flat_world(4);
+        let lantern = world.registry.block_named("jack_o_lantern").unwrap();
+        let source = glm::vec3(15, 4, 8);
+        let across_border = glm::vec3(18, 4, 8);
+        assert_eq!(block_light(&world, &across_border), 0);
+
+        world.set_block(&source, lantern);
+        assert_eq!(block_light(&world, &source), MAX_LIGHT);
+        assert_eq!(block_light(&world, &glm::vec3(16, 4, 8)), 14);
+        assert_eq!(block_light(&world, &across_border), 12);
+        assert_eq!(block_light(&world, &glm::vec3(15, 4, 5)), 12);
+
+        world.set_block(&source, Block::Air);
+        assert_eq!(block_light(&world, &source), 0);
+        assert_eq!(block_light(&world, &across_border), 0);
+    }
+
+    #[test]
+    fn opaque_blocks_stop_block_light() {
+        let mut world = flat_world(4);
+        let lantern = world.registry.block_named("jack_o_lantern").unwrap();
+        world.set_block(&glm::vec3(15, 4, 8), lantern);
+        world.set_block(&glm::vec3(16, 4, 8), Block::Cobblestone);
+        // The light goes around the wall, taking two extra steps
+        assert_eq!(block_light(&world, &glm::vec3(17, 4, 8)), 11);
+    }
+
+    #[test]
+    fn closing_a_sky_shaft_darkens_it() {
+        let mut world = flat_world(12);
+        for y in 4..12 {
+            world.set_block(&glm::vec3(8, y, 8), Block::Air);
+        }
+        let bottom = glm::vec3(8, 4, 8);
+        assert_eq!(sky_light(&world, &bottom), MAX_LIGHT);
+
+        world.set_block(&glm::vec3(8, 11, 8), Block::Dirt);
+        assert_eq!(sky_light(&world, &bottom), 0);
+        assert_eq!(sky_light(&world, &glm::vec3(8, 12, 8)), MAX_LIGHT);
+
+        world.set_block(&glm::vec3(8, 11, 8), Block::Air);
+        assert_eq!(sky_light(&world, &bottom), MAX_LIGHT);
+    }
+
+    #[test]
+    fn sky_light_fades_under_an_overhang() {
+        let mut world = flat_world(4);
+        world.set_block(&glm::vec3(8, 5, 8), Block::Dirt);
+        assert_eq!(sky_light(&world, &glm::vec3(8, 4, 8)), MAX_LIGHT - 1);
+    }
+}
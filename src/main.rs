@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use glutin::{
     event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
@@ -6,17 +6,103 @@ use glutin::{
     ContextBuilder,
 };
 
+mod aabb;
 mod app;
+mod bindings;
 mod block;
 mod camera;
+mod fluid;
+mod frustum;
+mod gamepad;
+mod headless;
+mod hud;
 mod input;
+mod inventory;
+mod light;
+mod mesh;
+mod player;
+mod profiler;
+mod raycast;
+mod registry;
+mod replay;
+mod save;
+mod shader;
 mod system;
+mod terrain;
+mod texture_pack;
+mod world;
 
-use app::App;
+use app::{App, WINDOW_TITLE};
+use camera::FreeCamera;
+use nalgebra_glm as glm;
+use texture_pack::DEFAULT_PACK;
+
+const SCREENSHOT_DIMENSIONS: [u32; 2] = [1280, 720];
+const USAGE: &str = "Usage: notminecraft \
+    [--screenshot <output.png> [--position <x,y,z>] [--look <yaw,pitch>]] \
+    [--texture-pack <pack>] \
+    [--record <recording.ron> | --replay <recording.ron> [--profile <frames.csv>]]";
+
+/// The value following a command line flag, if the flag was given
+fn flag_value<'a>(arguments: &'a [String], flag: &str) -> Result<Option<&'a str>> {
+    match arguments.iter().position(|argument| argument == flag) {
+        Some(index) => {
+            let value = arguments.get(index + 1).context(USAGE)?;
+            Ok(Some(value.as_str()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Parses a flag's value as a number of comma separated floats
+fn flag_numbers(arguments: &[String], flag: &str, count: usize) -> Result<Option<Vec<f32>>> {
+    let value = match flag_value(arguments, flag)? {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let numbers = value
+        .split(',')
+        .map(|number| number.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|numbers| numbers.len() == count)
+        .with_context(|| {
+            format!(
+                "{} takes {} comma separated numbers\n{}",
+                flag, count, USAGE
+            )
+        })?;
+    Ok(Some(numbers))
+}
+
+/// The camera given by the screenshot flags, starting from the default camera
+fn screenshot_camera(arguments: &[String]) -> Result<FreeCamera> {
+    let mut camera = FreeCamera::default();
+    if let Some(position) = flag_numbers(arguments, "--position", 3)? {
+        camera.set_position(glm::vec3(position[0], position[1], position[2]));
+    }
+    if let Some(look) = flag_numbers(arguments, "--look", 2)? {
+        camera.set_orientation(look[0], look[1]);
+    }
+    Ok(camera)
+}
 
 fn main() -> Result<()> {
+    let arguments = std::env::args().collect::<Vec<_>>();
+    let screenshot_path = flag_value(&arguments, "--screenshot")?;
+    let texture_pack = flag_value(&arguments, "--texture-pack")?.unwrap_or(DEFAULT_PACK);
+    if let Some(recording_path) = flag_value(&arguments, "--replay")? {
+        let profile_path = flag_value(&arguments, "--profile")?;
+        return headless::replay(recording_path, screenshot_path, profile_path, texture_pack);
+    }
+    if let Some(path) = screenshot_path {
+        let camera = screenshot_camera(&arguments)?;
+        return headless::render_screenshot(path, SCREENSHOT_DIMENSIONS, &camera, texture_pack);
+    }
+    let recording_path = flag_value(&arguments, "--record")?;
+
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().with_title("Not minecraft!");
+    let window = WindowBuilder::new().with_title(WINDOW_TITLE);
     let gl_window = ContextBuilder::new().build_windowed(window, &event_loop)?;
 
     let gl_window = unsafe {
@@ -28,7 +114,11 @@ fn main() -> Result<()> {
     gl::load_with(|symbol| gl_window.get_proc_address(symbol));
 
     let dimensions = gl_window.window().inner_size();
-    let mut app = App::new([dimensions.width, dimensions.height])?;
+    let dimensions = [dimensions.width, dimensions.height];
+    let mut app = match recording_path {
+        Some(path) => App::recording(dimensions, path, texture_pack)?,
+        None => App::new(dimensions, texture_pack)?,
+    };
 
     event_loop.run(move |event, _, control_flow| {
         let result = || -> Result<()> {
@@ -47,6 +137,7 @@ fn main() -> Result<()> {
                     gl_window.swap_buffers()?
                 }
                 Event::LoopDestroyed => {
+                    app.save()?;
                     return Ok(());
                 }
                 Event::WindowEvent { event, .. } => match event {
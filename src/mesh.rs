@@ -0,0 +1,725 @@
+use gl::types::*;
+use nalgebra_glm as glm;
+
+use crate::{
+    block::{Block, RenderLayer},
+    light::{LightChannel, MAX_LIGHT},
+    registry::BlockRegistry,
+    world::{Chunk, World, CHUNK_DEPTH, CHUNK_LENGTH, CHUNK_WIDTH},
+};
+
+const CHUNK_DIMENSIONS: [usize; 3] = [CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_LENGTH];
+/// Ambient occlusion of a vertex with no occluding neighbors
+pub const MAX_AMBIENT_OCCLUSION: u8 = 3;
+/// How much of the light a fully occluded vertex loses, matching block.fs.glsl
+const AMBIENT_OCCLUSION_STRENGTH: f32 = 0.6;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub layer: i32,
+    /// Sky and block light reaching the vertex, scaled to between 0 and 1
+    pub light: [f32; 2],
+    /// Ambient occlusion of the vertex, from 0 when fully occluded to 1 when unoccluded
+    pub ambient_occlusion: f32,
+}
+
+#[derive(Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+/// A chunk's geometry split by how it is drawn
+#[derive(Default)]
+pub struct ChunkMesh {
+    /// Opaque and cutout faces, drawn first with depth writes
+    pub opaque: Mesh,
+    /// Translucent faces, drawn afterwards from back to front with blending
+    pub translucent: Mesh,
+}
+
+impl Mesh {
+    /// Reorders the quads so the farthest from the eye come first,
+    /// with the eye in the same space as the vertices
+    pub fn sort_back_to_front(&mut self, eye: &glm::Vec3) {
+        let vertices = &self.vertices;
+        let mut quads = self
+            .indices
+            .chunks(6)
+            .map(|quad| {
+                // Each quad's four corners are stored one after another
+                let first = *quad.iter().min().unwrap_or(&0) as usize;
+                let center = vertices[first..first + 4]
+                    .iter()
+                    .fold(glm::Vec3::zeros(), |sum, vertex| {
+                        sum + glm::Vec3::from(vertex.position)
+                    })
+                    / 4.0;
+                (glm::distance2(&center, eye), quad)
+            })
+            .collect::<Vec<_>>();
+        quads.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        self.indices = quads
+            .into_iter()
+            .flat_map(|(_, quad)| quad.iter().copied())
+            .collect();
+    }
+
+    fn push_quad(
+        &mut self,
+        corners: [[f32; 3]; 4],
+        uvs: [[f32; 2]; 4],
+        layer: i32,
+        light: [u8; 2],
+        ambient_occlusion: [u8; 4],
+    ) {
+        let offset = self.vertices.len() as u32;
+        let light = [
+            light[0] as f32 / MAX_LIGHT as f32,
+            light[1] as f32 / MAX_LIGHT as f32,
+        ];
+        for ((position, uv), occlusion) in
+            corners.iter().zip(uvs.iter()).zip(ambient_occlusion.iter())
+        {
+            self.vertices.push(Vertex {
+                position: *position,
+                uv: *uv,
+                layer,
+                light,
+                ambient_occlusion: *occlusion as f32 / MAX_AMBIENT_OCCLUSION as f32,
+            });
+        }
+        let [first, second, third, fourth] = if flips_quad(&ambient_occlusion) {
+            [1, 2, 3, 0]
+        } else {
+            [0, 1, 2, 3]
+        };
+        self.indices.extend_from_slice(&[
+            offset + first,
+            offset + second,
+            offset + third,
+            offset + first,
+            offset + third,
+            offset + fourth,
+        ]);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Face {
+    Left,
+    Right,
+    Bottom,
+    Top,
+    Back,
+    Front,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Left,
+        Face::Right,
+        Face::Bottom,
+        Face::Top,
+        Face::Back,
+        Face::Front,
+    ];
+
+    /// The axis the face points along, with x = 0, y = 1 and z = 2
+    pub fn axis(&self) -> usize {
+        match *self {
+            Face::Left | Face::Right => 0,
+            Face::Bottom | Face::Top => 1,
+            Face::Back | Face::Front => 2,
+        }
+    }
+
+    pub fn is_positive(&self) -> bool {
+        matches!(*self, Face::Right | Face::Top | Face::Front)
+    }
+
+    pub fn normal(&self) -> glm::IVec3 {
+        let mut normal = glm::IVec3::zeros();
+        normal[self.axis()] = if self.is_positive() { 1 } else { -1 };
+        normal
+    }
+
+    /// The two axes spanning the face, in the order they are swept by the mesher
+    fn tangents(&self) -> (usize, usize) {
+        match self.axis() {
+            0 => (2, 1),
+            1 => (0, 2),
+            _ => (0, 1),
+        }
+    }
+
+    /// Texture coordinates of a point on the face.
+    /// These match the orientation of the original per-face cube vertices,
+    /// so a merged quad repeats its tile once per block.
+    fn uv(&self, position: [f32; 3]) -> [f32; 2] {
+        let [x, y, z] = position;
+        match *self {
+            Face::Left => [z, y],
+            Face::Right => [-z, y],
+            Face::Bottom | Face::Top => [x, -z],
+            Face::Back | Face::Front => [x, y],
+        }
+    }
+
+    fn tile(&self, registry: &BlockRegistry, block: Block) -> i32 {
+        let configuration = registry.configuration(block);
+        match *self {
+            Face::Left => configuration.left,
+            Face::Right => configuration.right,
+            Face::Bottom => configuration.bottom,
+            Face::Top => configuration.top,
+            Face::Back => configuration.back,
+            Face::Front => configuration.front,
+        }
+    }
+}
+
+fn is_opaque(registry: &BlockRegistry, block: Block) -> bool {
+    registry.configuration(block).is_opaque()
+}
+
+/// Whether a face of a cube is hidden by the block it faces.
+/// Besides opaque blocks, a translucent block hides its faces against the
+/// same block so the inside of a body of water or glass isn't drawn.
+/// Fluid blocks count as the same block whatever their levels.
+fn is_face_hidden(registry: &BlockRegistry, block: Block, neighbor: Option<Block>) -> bool {
+    match neighbor {
+        Some(neighbor) => {
+            let same_fluid = match (block.fluid(), neighbor.fluid()) {
+                (Some((fluid, _)), Some((other, _))) => fluid == other,
+                _ => false,
+            };
+            is_opaque(registry, neighbor)
+                || ((neighbor == block || same_fluid)
+                    && registry.configuration(block).render_layer != RenderLayer::Cutout)
+        }
+        None => false,
+    }
+}
+
+/// Sky and block light at a position local to the chunk.
+/// Anywhere outside the loaded world is treated as open sky.
+fn light_levels(chunk: &Chunk, world: &World, position: &glm::IVec3) -> [u8; 2] {
+    let levels = |channel| {
+        if chunk.get_block(position).is_some() {
+            Some(chunk.light(position, channel))
+        } else {
+            world.light_at(&(chunk.origin() + position), channel)
+        }
+    };
+    match (levels(LightChannel::Sky), levels(LightChannel::Block)) {
+        (Some(sky), Some(block)) => [sky, block],
+        _ => [MAX_LIGHT, 0],
+    }
+}
+
+/// Ambient occlusion at a corner of a face, from the three voxels in front of the face
+/// that touch the corner: the two along its edges and the one diagonally across.
+/// Returns 0 when fully occluded and `MAX_AMBIENT_OCCLUSION` when nothing is in the way.
+pub fn vertex_ambient_occlusion(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        return 0;
+    }
+    MAX_AMBIENT_OCCLUSION - (side1 as u8 + side2 as u8 + corner as u8)
+}
+
+/// Whether a quad should be split along the diagonal from its second to its fourth corner.
+/// The split follows the brighter diagonal so occlusion is interpolated
+/// the same way whichever way the quad is rotated.
+pub fn flips_quad(ambient_occlusion: &[u8; 4]) -> bool {
+    ambient_occlusion[1] as u16 + ambient_occlusion[3] as u16
+        > ambient_occlusion[0] as u16 + ambient_occlusion[2] as u16
+}
+
+/// The fraction of light a vertex receives at an ambient occlusion between 0 and 1
+pub fn ambient_occlusion_brightness(ambient_occlusion: f32) -> f32 {
+    1.0 - AMBIENT_OCCLUSION_STRENGTH * (1.0 - ambient_occlusion)
+}
+
+/// The block at a position local to the chunk, looked up in the world past its borders
+fn block_near(chunk: &Chunk, world: &World, position: &glm::IVec3) -> Option<Block> {
+    chunk
+        .get_block(position)
+        .or_else(|| world.block_at(&(chunk.origin() + position)))
+}
+
+/// A face in the greedy meshing mask.
+/// Only faces that look identical can be merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MaskedFace {
+    tile: i32,
+    translucent: bool,
+    light: [u8; 2],
+    /// In the order the corners are swept by the mesher, before any winding change
+    ambient_occlusion: [u8; 4],
+}
+
+impl MaskedFace {
+    /// Merged quads stretch their corner occlusion across every face they cover,
+    /// so faces only merge along an axis their occlusion doesn't vary on
+    fn merges_along_u(&self) -> bool {
+        let occlusion = self.ambient_occlusion;
+        occlusion[0] == occlusion[1] && occlusion[3] == occlusion[2]
+    }
+
+    fn merges_along_v(&self) -> bool {
+        let occlusion = self.ambient_occlusion;
+        occlusion[0] == occlusion[3] && occlusion[1] == occlusion[2]
+    }
+}
+
+/// Builds the geometry for a chunk, merging coplanar faces
+/// that share a tile, light level and ambient occlusion into as few quads as possible.
+/// Faces on the chunk border are culled against neighboring chunks in the world.
+/// Positions are relative to the chunk's origin.
+pub fn mesh_chunk(chunk: &Chunk, world: &World) -> ChunkMesh {
+    let mut mesh = ChunkMesh::default();
+    for face in Face::ALL.iter() {
+        mesh_faces(chunk, world, *face, &mut mesh);
+    }
+    mesh_entities(chunk, world, &mut mesh);
+    mesh
+}
+
+fn mesh_faces(chunk: &Chunk, world: &World, face: Face, mesh: &mut ChunkMesh) {
+    let axis = face.axis();
+    let (u_axis, v_axis) = face.tangents();
+    let normal = face.normal();
+    let (width, height) = (CHUNK_DIMENSIONS[u_axis], CHUNK_DIMENSIONS[v_axis]);
+
+    let mut u_direction = glm::IVec3::zeros();
+    let mut v_direction = glm::IVec3::zeros();
+    u_direction[u_axis] = 1;
+    v_direction[v_axis] = 1;
+    let occludes = |position: glm::IVec3| match block_near(chunk, world, &position) {
+        Some(block) => is_opaque(&world.registry, block),
+        None => false,
+    };
+
+    let mut mask: Vec<Option<MaskedFace>> = vec![None; width * height];
+    for slice in 0..CHUNK_DIMENSIONS[axis] {
+        for v in 0..height {
+            for u in 0..width {
+                let mut position = glm::IVec3::zeros();
+                position[axis] = slice as i32;
+                position[u_axis] = u as i32;
+                position[v_axis] = v as i32;
+
+                let block = chunk.block(&position);
+                let configuration = world.registry.configuration(block);
+                let neighbor = position + normal;
+                if !configuration.is_cube()
+                    || is_face_hidden(&world.registry, block, block_near(chunk, world, &neighbor))
+                {
+                    mask[v * width + u] = None;
+                    continue;
+                }
+
+                let mut ambient_occlusion = [0; 4];
+                let corner_directions = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+                for (occlusion, (u_sign, v_sign)) in
+                    ambient_occlusion.iter_mut().zip(corner_directions.iter())
+                {
+                    let side1 = neighbor + u_direction * *u_sign;
+                    let side2 = neighbor + v_direction * *v_sign;
+                    let corner = side1 + v_direction * *v_sign;
+                    *occlusion = vertex_ambient_occlusion(
+                        occludes(side1),
+                        occludes(side2),
+                        occludes(corner),
+                    );
+                }
+
+                mask[v * width + u] = Some(MaskedFace {
+                    tile: face.tile(&world.registry, block),
+                    translucent: configuration.render_layer == RenderLayer::Translucent,
+                    light: light_levels(chunk, world, &neighbor),
+                    ambient_occlusion,
+                });
+            }
+        }
+
+        for v in 0..height {
+            let mut u = 0;
+            while u < width {
+                let key = match mask[v * width + u] {
+                    Some(key) => key,
+                    None => {
+                        u += 1;
+                        continue;
+                    }
+                };
+
+                let mut quad_width = 1;
+                while key.merges_along_u()
+                    && u + quad_width < width
+                    && mask[v * width + u + quad_width] == Some(key)
+                {
+                    quad_width += 1;
+                }
+
+                let mut quad_height = 1;
+                'grow: while key.merges_along_v() && v + quad_height < height {
+                    for offset in 0..quad_width {
+                        if mask[(v + quad_height) * width + u + offset] != Some(key) {
+                            break 'grow;
+                        }
+                    }
+                    quad_height += 1;
+                }
+
+                for row in 0..quad_height {
+                    for column in 0..quad_width {
+                        mask[(v + row) * width + u + column] = None;
+                    }
+                }
+
+                let mut base = [0.0; 3];
+                base[axis] = slice as f32 + if face.is_positive() { 1.0 } else { 0.0 };
+                base[u_axis] = u as f32;
+                base[v_axis] = v as f32;
+
+                let mut corners = [base; 4];
+                corners[1][u_axis] += quad_width as f32;
+                corners[2][u_axis] += quad_width as f32;
+                corners[2][v_axis] += quad_height as f32;
+                corners[3][v_axis] += quad_height as f32;
+
+                // Keep counter-clockwise winding when viewed from outside the block
+                let mut ambient_occlusion = key.ambient_occlusion;
+                if !faces_outward(face) {
+                    corners.swap(1, 3);
+                    ambient_occlusion.swap(1, 3);
+                }
+
+                let uvs = [
+                    face.uv(corners[0]),
+                    face.uv(corners[1]),
+                    face.uv(corners[2]),
+                    face.uv(corners[3]),
+                ];
+                let mesh = if key.translucent {
+                    &mut mesh.translucent
+                } else {
+                    &mut mesh.opaque
+                };
+                mesh.push_quad(corners, uvs, key.tile, key.light, ambient_occlusion);
+
+                u += quad_width;
+            }
+        }
+    }
+}
+
+/// Whether sweeping the u axis then the v axis produces an outward facing quad
+fn faces_outward(face: Face) -> bool {
+    let (u_axis, v_axis) = face.tangents();
+    let mut u = glm::IVec3::zeros();
+    let mut v = glm::IVec3::zeros();
+    u[u_axis] = 1;
+    v[v_axis] = 1;
+    u.cross(&v).dot(&face.normal()) > 0
+}
+
+fn mesh_entities(chunk: &Chunk, world: &World, mesh: &mut ChunkMesh) {
+    for x in 0..CHUNK_WIDTH {
+        for z in 0..CHUNK_LENGTH {
+            for y in 0..CHUNK_DEPTH {
+                let configuration = world.registry.configuration(chunk.blocks[x][z][y]);
+                if !configuration.is_entity {
+                    continue;
+                }
+                let mesh = if configuration.render_layer == RenderLayer::Translucent {
+                    &mut mesh.translucent
+                } else {
+                    &mut mesh.opaque
+                };
+
+                let light = light_levels(chunk, world, &glm::vec3(x as i32, y as i32, z as i32));
+
+                let (x, y, z) = (x as f32, y as f32, z as f32);
+                let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+                // Two quads crossing through the center of the block
+                let center_z = z + 0.5;
+                mesh.push_quad(
+                    [
+                        [x, y, center_z],
+                        [x + 1.0, y, center_z],
+                        [x + 1.0, y + 1.0, center_z],
+                        [x, y + 1.0, center_z],
+                    ],
+                    uvs,
+                    configuration.front,
+                    light,
+                    [MAX_AMBIENT_OCCLUSION; 4],
+                );
+
+                let center_x = x + 0.5;
+                mesh.push_quad(
+                    [
+                        [center_x, y, z],
+                        [center_x, y, z + 1.0],
+                        [center_x, y + 1.0, z + 1.0],
+                        [center_x, y + 1.0, z],
+                    ],
+                    uvs,
+                    configuration.front,
+                    light,
+                    [MAX_AMBIENT_OCCLUSION; 4],
+                );
+            }
+        }
+    }
+}
+
+pub struct GpuMesh {
+    vao: GLuint,
+    vbo: GLuint,
+    ebo: GLuint,
+    index_count: usize,
+}
+
+impl GpuMesh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let mut vao = 0;
+        let mut vbo = 0;
+        let mut ebo = 0;
+        let stride = std::mem::size_of::<Vertex>() as GLsizei;
+        let float_size = std::mem::size_of::<GLfloat>();
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (mesh.vertices.len() * std::mem::size_of::<Vertex>()) as GLsizeiptr,
+                mesh.vertices.as_ptr() as *const GLvoid,
+                gl::STATIC_DRAW,
+            );
+
+            gl::GenBuffers(1, &mut ebo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (mesh.indices.len() * std::mem::size_of::<u32>()) as GLsizeiptr,
+                mesh.indices.as_ptr() as *const GLvoid,
+                gl::STATIC_DRAW,
+            );
+
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * float_size) as *const GLvoid,
+            );
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribIPointer(2, 1, gl::INT, stride, (5 * float_size) as *const GLvoid);
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(
+                3,
+                2,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (6 * float_size) as *const GLvoid,
+            );
+            gl::EnableVertexAttribArray(4);
+            gl::VertexAttribPointer(
+                4,
+                1,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (8 * float_size) as *const GLvoid,
+            );
+
+            gl::BindVertexArray(0);
+        }
+        Self {
+            vao,
+            vbo,
+            ebo,
+            index_count: mesh.indices.len(),
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.index_count / 3
+    }
+
+    /// Replaces the indices with the same number of reordered ones
+    pub fn update_indices(&mut self, indices: &[u32]) {
+        debug_assert_eq!(indices.len(), self.index_count);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferSubData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                0,
+                std::mem::size_of_val(indices) as GLsizeiptr,
+                indices.as_ptr() as *const GLvoid,
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+
+    pub unsafe fn draw(&self) {
+        if self.index_count == 0 {
+            return;
+        }
+        gl::BindVertexArray(self.vao);
+        gl::DrawElements(
+            gl::TRIANGLES,
+            self.index_count as _,
+            gl::UNSIGNED_INT,
+            std::ptr::null(),
+        );
+    }
+}
+
+impl Drop for GpuMesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        terrain::{FlatTerrainGenerator, TerrainGenerator},
+        world::test_world,
+    };
+
+    fn quads(mesh: &Mesh) -> usize {
+        mesh.indices.len() / 6
+    }
+
+    #[test]
+    fn corner_occlusion_matches_known_configurations() {
+        // (side1, side2, corner), expected occlusion
+        let cases = [
+            ((true, true, false), 0),
+            ((true, true, true), 0),
+            ((true, false, false), 2),
+            ((false, true, false), 2),
+            ((false, false, true), 2),
+            ((true, false, true), 1),
+            ((false, true, true), 1),
+            ((false, false, false), MAX_AMBIENT_OCCLUSION),
+        ];
+        for ((side1, side2, corner), expected) in cases.iter() {
+            assert_eq!(
+                vertex_ambient_occlusion(*side1, *side2, *corner),
+                *expected,
+                "side1 {}, side2 {}, corner {}",
+                side1,
+                side2,
+                corner
+            );
+        }
+    }
+
+    #[test]
+    fn quads_split_along_the_brighter_diagonal() {
+        assert!(!flips_quad(&[3, 3, 3, 3]));
+        assert!(flips_quad(&[0, 3, 3, 3]));
+        assert!(flips_quad(&[3, 3, 2, 3]));
+        assert!(!flips_quad(&[3, 0, 3, 3]));
+        assert!(!flips_quad(&[3, 3, 3, 1]));
+
+        let mut mesh = Mesh::default();
+        let corners = [[0.0; 3]; 4];
+        let uvs = [[0.0; 2]; 4];
+        mesh.push_quad(corners, uvs, 0, [MAX_LIGHT, 0], [3, 3, 3, 3]);
+        mesh.push_quad(corners, uvs, 0, [MAX_LIGHT, 0], [0, 3, 3, 3]);
+        assert_eq!(mesh.indices[..6], [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.indices[6..], [5, 6, 7, 5, 7, 4]);
+    }
+
+    #[test]
+    fn occluded_corners_darken_the_ground_beside_a_wall() {
+        let mut world = test_world(FlatTerrainGenerator { height: 1 });
+        let position = glm::vec2(0, 0);
+        let mut chunk = FlatTerrainGenerator { height: 1 }.generate(&position);
+        chunk.blocks[8][8][1] = Block::Cobblestone;
+        world.insert_chunk(position, chunk);
+
+        // Ground quads touching the block along an edge or at a corner
+        // lose one level of occlusion at the vertices they share with it
+        let mesh = mesh_chunk(&world.chunks[&position], &world);
+        let occlusion_at = |x: f32, z: f32| {
+            mesh.opaque
+                .vertices
+                .chunks(4)
+                .filter(|quad| quad.iter().all(|vertex| vertex.position[1] == 1.0))
+                .flatten()
+                .filter(|vertex| vertex.position == [x, 1.0, z])
+                .map(|vertex| vertex.ambient_occlusion)
+                .fold(1.0, f32::min)
+        };
+        assert_eq!(occlusion_at(8.0, 8.0), 2.0 / 3.0);
+        assert_eq!(occlusion_at(4.0, 4.0), 1.0);
+    }
+
+    #[test]
+    fn lone_block_has_a_quad_per_face() {
+        let mut world = test_world(FlatTerrainGenerator { height: 0 });
+        let position = glm::vec2(0, 0);
+        let mut chunk = Chunk::new(&position);
+        chunk.blocks[8][8][8] = Block::Dirt;
+        world.insert_chunk(position, chunk);
+
+        let mesh = mesh_chunk(&world.chunks[&position], &world);
+        assert_eq!(quads(&mesh.opaque), 6);
+        assert_eq!(mesh.opaque.vertices.len(), 24);
+        assert!(mesh.translucent.indices.is_empty());
+    }
+
+    #[test]
+    fn flat_layer_merges_into_a_quad_per_side() {
+        let mut world = test_world(FlatTerrainGenerator { height: 1 });
+        world.view_distance = 1;
+        world.update(&glm::vec3(8.0, 10.0, 8.0));
+        world.finish_loading();
+        assert_eq!(world.chunks.len(), 5);
+
+        // The neighbors hide the layer's edges, leaving the top and bottom
+        let mesh = mesh_chunk(&world.chunks[&glm::vec2(0, 0)], &world);
+        assert_eq!(quads(&mesh.opaque), 2);
+        assert_eq!(mesh.opaque.vertices.len(), 8);
+    }
+
+    #[test]
+    fn unloaded_neighbors_leave_border_faces() {
+        let mut world = test_world(FlatTerrainGenerator { height: 1 });
+        let position = glm::vec2(0, 0);
+        world.insert_chunk(
+            position,
+            FlatTerrainGenerator { height: 1 }.generate(&position),
+        );
+
+        let mesh = mesh_chunk(&world.chunks[&position], &world);
+        assert_eq!(quads(&mesh.opaque), 6);
+    }
+}
@@ -0,0 +1,288 @@
+use nalgebra_glm as glm;
+
+use crate::{
+    aabb::{Aabb, EPSILON},
+    world::World,
+};
+
+const WIDTH: f32 = 0.6;
+const HEIGHT: f32 = 1.8;
+const EYE_HEIGHT: f32 = 1.62;
+const SNEAK_EYE_HEIGHT: f32 = 1.32;
+const WALK_SPEED: f32 = 4.3;
+const SNEAK_SPEED: f32 = 1.3;
+const GRAVITY: f32 = 28.0;
+const TERMINAL_VELOCITY: f32 = 60.0;
+const JUMP_VELOCITY: f32 = 8.5;
+
+/// What the player wants to do during a physics step
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlayerInput {
+    /// Forward and rightward movement, each in [-1, 1]
+    pub movement: glm::Vec2,
+    /// The direction the player is looking. Only its horizontal component is used.
+    pub facing: glm::Vec3,
+    pub jump: bool,
+    pub sneak: bool,
+}
+
+pub struct Player {
+    /// The center of the bottom of the player's bounding box
+    pub position: glm::Vec3,
+    pub velocity: glm::Vec3,
+    pub on_ground: bool,
+    pub sneaking: bool,
+}
+
+impl Player {
+    pub fn new(position: glm::Vec3) -> Self {
+        Self {
+            position,
+            velocity: glm::Vec3::zeros(),
+            on_ground: false,
+            sneaking: false,
+        }
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let half_width = WIDTH / 2.0;
+        Aabb {
+            min: self.position - glm::vec3(half_width, 0.0, half_width),
+            max: self.position + glm::vec3(half_width, HEIGHT, half_width),
+        }
+    }
+
+    pub fn eye_position(&self) -> glm::Vec3 {
+        let eye_height = if self.sneaking {
+            SNEAK_EYE_HEIGHT
+        } else {
+            EYE_HEIGHT
+        };
+        self.position + glm::vec3(0.0, eye_height, 0.0)
+    }
+
+    /// Advances the player by one fixed physics step
+    pub fn step(&mut self, world: &World, input: &PlayerInput, delta_time: f32) {
+        self.sneaking = input.sneak;
+
+        let forward = glm::vec3(input.facing.x, 0.0, input.facing.z);
+        let forward = if forward.norm_squared() > 0.0 {
+            forward.normalize()
+        } else {
+            glm::Vec3::zeros()
+        };
+        let right = glm::vec3(-forward.z, 0.0, forward.x);
+        let mut wish_direction = forward * input.movement.x + right * input.movement.y;
+        if wish_direction.norm_squared() > 1.0 {
+            wish_direction = wish_direction.normalize();
+        }
+        let speed = if input.sneak { SNEAK_SPEED } else { WALK_SPEED };
+        self.velocity.x = wish_direction.x * speed;
+        self.velocity.z = wish_direction.z * speed;
+
+        if input.jump && self.on_ground {
+            self.velocity.y = JUMP_VELOCITY;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * delta_time).max(-TERMINAL_VELOCITY);
+
+        let mut motion = self.velocity * delta_time;
+
+        let aabb = self.aabb();
+        motion.y = sweep_axis(world, &aabb, 1, motion.y);
+        let aabb = aabb.translated(&glm::vec3(0.0, motion.y, 0.0));
+
+        motion.x = sweep_axis(world, &aabb, 0, motion.x);
+        if input.sneak
+            && self.on_ground
+            && !has_ground(world, &aabb, &glm::vec3(motion.x, 0.0, 0.0))
+        {
+            motion.x = 0.0;
+        }
+        let aabb = aabb.translated(&glm::vec3(motion.x, 0.0, 0.0));
+
+        motion.z = sweep_axis(world, &aabb, 2, motion.z);
+        if input.sneak
+            && self.on_ground
+            && !has_ground(world, &aabb, &glm::vec3(0.0, 0.0, motion.z))
+        {
+            motion.z = 0.0;
+        }
+
+        let falling = self.velocity.y < 0.0;
+        let blocked_vertically = motion.y != self.velocity.y * delta_time;
+        self.on_ground = falling && blocked_vertically;
+        if blocked_vertically {
+            self.velocity.y = 0.0;
+        }
+
+        self.position += motion;
+    }
+}
+
+/// Unloaded chunks count as solid so the player can't fall out of the world while they stream in
+fn is_solid(world: &World, position: &glm::IVec3) -> bool {
+    match world.block_at(position) {
+        Some(block) => world.registry.configuration(block).is_solid,
+        None => true,
+    }
+}
+
+/// Clamps movement along one axis so the box stops at the first solid block in its path
+pub fn sweep_axis(world: &World, aabb: &Aabb, axis: usize, motion: f32) -> f32 {
+    if motion == 0.0 {
+        return 0.0;
+    }
+
+    let mut swept = *aabb;
+    if motion > 0.0 {
+        swept.max[axis] += motion;
+    } else {
+        swept.min[axis] += motion;
+    }
+
+    let mut motion = motion;
+    for position in swept.block_positions() {
+        if !is_solid(world, &position) {
+            continue;
+        }
+        let block = Aabb::of_block(&position);
+        if motion > 0.0 && aabb.max[axis] <= block.min[axis] + EPSILON {
+            motion = motion
+                .min(block.min[axis] - aabb.max[axis] - EPSILON)
+                .max(0.0);
+        } else if motion < 0.0 && aabb.min[axis] >= block.max[axis] - EPSILON {
+            motion = motion
+                .max(block.max[axis] - aabb.min[axis] + EPSILON)
+                .min(0.0);
+        }
+    }
+    motion
+}
+
+/// Whether the box would still be standing on something after a horizontal move
+fn has_ground(world: &World, aabb: &Aabb, offset: &glm::Vec3) -> bool {
+    let below = aabb.translated(&(offset - glm::vec3(0.0, 0.1, 0.0)));
+    let feet = Aabb {
+        min: below.min,
+        max: glm::vec3(below.max.x, aabb.min.y, below.max.z),
+    };
+    feet.block_positions()
+        .iter()
+        .any(|position| is_solid(world, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block::Block, terrain::FlatTerrainGenerator, world::test_world};
+
+    const STEP: f32 = 1.0 / 60.0;
+    /// The top of the ground in a flat world
+    const GROUND: f32 = 4.0;
+
+    /// Loads the flat chunks around the origin
+    fn flat_world() -> World {
+        let mut world = test_world(FlatTerrainGenerator {
+            height: GROUND as usize,
+        });
+        world.view_distance = 1;
+        world.update(&glm::vec3(8.0, GROUND, 8.0));
+        world.finish_loading();
+        world
+    }
+
+    fn walk_east(sneak: bool) -> PlayerInput {
+        PlayerInput {
+            movement: glm::vec2(1.0, 0.0),
+            facing: glm::vec3(1.0, 0.0, 0.0),
+            sneak,
+            ..Default::default()
+        }
+    }
+
+    fn run(player: &mut Player, world: &World, input: &PlayerInput, seconds: f32) {
+        for _ in 0..(seconds / STEP).round() as usize {
+            player.step(world, input, STEP);
+        }
+    }
+
+    #[test]
+    fn lands_on_the_ground() {
+        let world = flat_world();
+        let mut player = Player::new(glm::vec3(8.5, GROUND + 2.0, 8.5));
+        player.step(&world, &PlayerInput::default(), STEP);
+        assert!(!player.on_ground);
+        assert!(player.velocity.y < 0.0);
+
+        run(&mut player, &world, &PlayerInput::default(), 1.0);
+        assert!(player.on_ground);
+        assert_eq!(player.velocity.y, 0.0);
+        assert!((player.position.y - (GROUND + EPSILON)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn jumps_only_from_the_ground() {
+        let world = flat_world();
+        let jump = PlayerInput {
+            jump: true,
+            ..Default::default()
+        };
+        let mut player = Player::new(glm::vec3(8.5, GROUND + 2.0, 8.5));
+        player.step(&world, &jump, STEP);
+        assert!(player.velocity.y < 0.0);
+
+        run(&mut player, &world, &PlayerInput::default(), 1.0);
+        player.step(&world, &jump, STEP);
+        assert!(player.velocity.y > 0.0);
+        assert!(!player.on_ground);
+        player.step(&world, &jump, STEP);
+        assert!(player.velocity.y < JUMP_VELOCITY - GRAVITY * STEP);
+    }
+
+    #[test]
+    fn walls_stop_the_player_short_of_touching() {
+        let mut world = flat_world();
+        for y in 4..6 {
+            assert!(world.set_block(&glm::vec3(10, y, 8), Block::Cobblestone));
+        }
+        let mut player = Player::new(glm::vec3(8.5, GROUND, 8.5));
+        run(&mut player, &world, &walk_east(false), 1.0);
+        let gap = 10.0 - player.aabb().max.x;
+        assert!((gap - EPSILON).abs() < 1e-4, "gap {}", gap);
+        assert_eq!(player.velocity.x, WALK_SPEED);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn sneaking_keeps_the_player_on_ledges() {
+        let mut world = flat_world();
+        for x in 10..14 {
+            for z in 6..12 {
+                assert!(world.set_block(&glm::vec3(x, 3, z), Block::Air));
+            }
+        }
+
+        let mut player = Player::new(glm::vec3(8.5, GROUND, 8.5));
+        run(&mut player, &world, &PlayerInput::default(), 0.1);
+        run(&mut player, &world, &walk_east(true), 2.0);
+        assert!(player.on_ground);
+        assert!((player.position.y - GROUND).abs() < 0.01);
+        assert!(player.aabb().min.x < 10.0);
+        assert!(player.aabb().min.x > 9.9);
+
+        run(&mut player, &world, &walk_east(false), 1.0);
+        assert!(player.position.y < GROUND - 0.5);
+    }
+
+    #[test]
+    fn unloaded_chunks_are_solid() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        assert!(world.chunks.is_empty());
+        let mut player = Player::new(glm::vec3(8.5, 20.0, 8.5));
+        run(&mut player, &world, &walk_east(false), 0.5);
+        assert!(player.on_ground);
+        assert_eq!(player.position.y, 20.0);
+        let gap = 9.0 - player.aabb().max.x;
+        assert!((gap - EPSILON).abs() < 1e-4, "gap {}", gap);
+    }
+}
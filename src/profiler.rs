@@ -0,0 +1,222 @@
+use anyhow::{Context, Result};
+use std::{collections::VecDeque, fs, io::Write};
+
+/// How many recent frames the percentiles and CSV dumps cover
+const FRAME_HISTORY: usize = 600;
+/// How many recent chunk mesh builds the mesh timing percentiles cover
+const MESH_HISTORY: usize = 256;
+
+/// What happened during one frame
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameSample {
+    /// Counted from the first frame the profiler saw
+    pub frame: u64,
+    /// Seconds from the start of the frame to the start of the next one
+    pub frame_time: f64,
+    pub draw_calls: usize,
+    pub triangles: usize,
+    pub chunks_loaded: usize,
+    pub chunks_meshed: usize,
+    /// Seconds spent building chunk meshes
+    pub mesh_time: f64,
+}
+
+/// Collects frame timings and rendering counts. Nothing here measures time itself,
+/// so it works the same in the game, headless renders and benchmarks.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    samples: VecDeque<FrameSample>,
+    mesh_times: VecDeque<f64>,
+    current: FrameSample,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_draw(&mut self, triangles: usize) {
+        self.current.draw_calls += 1;
+        self.current.triangles += triangles;
+    }
+
+    pub fn record_mesh_build(&mut self, seconds: f64) {
+        self.current.chunks_meshed += 1;
+        self.current.mesh_time += seconds;
+        if self.mesh_times.len() == MESH_HISTORY {
+            self.mesh_times.pop_front();
+        }
+        self.mesh_times.push_back(seconds);
+    }
+
+    /// Closes the frame that just ended and starts counting the next one
+    pub fn finish_frame(&mut self, frame_time: f64, chunks_loaded: usize) {
+        let next_frame = self.current.frame + 1;
+        self.current.frame_time = frame_time;
+        self.current.chunks_loaded = chunks_loaded;
+        if self.samples.len() == FRAME_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(self.current);
+        self.current = FrameSample {
+            frame: next_frame,
+            ..FrameSample::default()
+        };
+    }
+
+    pub fn last_frame(&self) -> Option<&FrameSample> {
+        self.samples.back()
+    }
+
+    /// The frame time in seconds that the given percent of recent frames were at or below
+    pub fn frame_time_percentile(&self, percent: f64) -> Option<f64> {
+        let frame_times = self
+            .samples
+            .iter()
+            .map(|sample| sample.frame_time)
+            .collect::<Vec<_>>();
+        percentile(&frame_times, percent)
+    }
+
+    /// The time in seconds that the given percent of recent chunk mesh builds took at most
+    pub fn mesh_time_percentile(&self, percent: f64) -> Option<f64> {
+        let mesh_times = self.mesh_times.iter().copied().collect::<Vec<_>>();
+        percentile(&mesh_times, percent)
+    }
+
+    /// Lines describing the recent frames, for the debug overlay
+    pub fn summary(&self) -> Vec<String> {
+        let milliseconds = |seconds: Option<f64>| match seconds {
+            Some(seconds) => format!("{:.1}", seconds * 1000.0),
+            None => "-".to_string(),
+        };
+        let last = self.last_frame().copied().unwrap_or_default();
+        vec![
+            format!(
+                "Frame {} ms, p50 {} p95 {} p99 {}",
+                milliseconds(self.last_frame().map(|sample| sample.frame_time)),
+                milliseconds(self.frame_time_percentile(50.0)),
+                milliseconds(self.frame_time_percentile(95.0)),
+                milliseconds(self.frame_time_percentile(99.0)),
+            ),
+            format!(
+                "{} draw calls, {} triangles",
+                last.draw_calls, last.triangles
+            ),
+            format!(
+                "{} chunks loaded, {} meshed",
+                last.chunks_loaded, last.chunks_meshed
+            ),
+            format!(
+                "Mesh build p50 {} p95 {} ms",
+                milliseconds(self.mesh_time_percentile(50.0)),
+                milliseconds(self.mesh_time_percentile(95.0)),
+            ),
+        ]
+    }
+
+    /// Writes the recent frames as CSV with a header row, times in milliseconds
+    pub fn write_csv(&self, writer: &mut impl Write) -> Result<()> {
+        writeln!(
+            writer,
+            "frame,frame_time_ms,draw_calls,triangles,chunks_loaded,chunks_meshed,mesh_time_ms"
+        )?;
+        for sample in self.samples.iter() {
+            writeln!(
+                writer,
+                "{},{:.3},{},{},{},{},{:.3}",
+                sample.frame,
+                sample.frame_time * 1000.0,
+                sample.draw_calls,
+                sample.triangles,
+                sample.chunks_loaded,
+                sample.chunks_meshed,
+                sample.mesh_time * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn save_csv(&self, path: &str) -> Result<()> {
+        let mut bytes = Vec::new();
+        self.write_csv(&mut bytes)?;
+        fs::write(path, bytes).with_context(|| format!("Failed to write profile '{}'", path))
+    }
+}
+
+/// The smallest value that at least the given percent of values are at or below
+pub fn percentile(values: &[f64], percent: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_of_edge_inputs() {
+        assert_eq!(percentile(&[], 50.0), None);
+        for percent in [0.0, 50.0, 100.0].iter() {
+            assert_eq!(percentile(&[4.0], *percent), Some(4.0));
+        }
+        let values = [3.0, 1.0, 4.0, 1.5, 9.0];
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&values, 40.0), Some(1.5));
+        assert_eq!(percentile(&values, 41.0), Some(3.0));
+        assert_eq!(percentile(&values, 100.0), Some(9.0));
+    }
+
+    #[test]
+    fn history_keeps_the_newest_samples() {
+        let mut profiler = Profiler::new();
+        for frame in 0..FRAME_HISTORY + 10 {
+            profiler.record_mesh_build(frame as f64);
+            profiler.finish_frame(frame as f64, 0);
+        }
+        assert_eq!(profiler.samples.len(), FRAME_HISTORY);
+        assert_eq!(profiler.samples.front().unwrap().frame, 10);
+        assert_eq!(profiler.frame_time_percentile(0.0), Some(10.0));
+        assert_eq!(profiler.mesh_times.len(), MESH_HISTORY);
+        let oldest_mesh = (FRAME_HISTORY + 10 - MESH_HISTORY) as f64;
+        assert_eq!(profiler.mesh_time_percentile(0.0), Some(oldest_mesh));
+        assert_eq!(
+            profiler.last_frame().unwrap().frame,
+            (FRAME_HISTORY + 9) as u64
+        );
+    }
+
+    #[test]
+    fn csv_has_a_header_and_a_row_per_frame() {
+        let mut profiler = Profiler::new();
+        profiler.record_draw(12);
+        profiler.record_draw(30);
+        profiler.record_mesh_build(0.002);
+        profiler.finish_frame(0.016, 9);
+        profiler.finish_frame(0.0205, 9);
+
+        let mut bytes = Vec::new();
+        profiler.write_csv(&mut bytes).unwrap();
+        let csv = String::from_utf8(bytes).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            [
+                "frame,frame_time_ms,draw_calls,triangles,chunks_loaded,chunks_meshed,mesh_time_ms",
+                "0,16.000,2,42,9,1,2.000",
+                "1,20.500,0,0,9,0,0.000",
+            ]
+        );
+    }
+
+    #[test]
+    fn summary_of_no_frames() {
+        let summary = Profiler::new().summary();
+        assert_eq!(summary[0], "Frame - ms, p50 - p95 - p99 -");
+        assert_eq!(summary[3], "Mesh build p50 - p95 - ms");
+    }
+}
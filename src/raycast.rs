@@ -0,0 +1,160 @@
+use nalgebra_glm as glm;
+
+use crate::{
+    block::Block,
+    world::{block_position_of, World},
+};
+
+pub struct RaycastHit {
+    pub block: Block,
+    pub position: glm::IVec3,
+    /// Points out of the face that was hit. Zero if the ray started inside the block.
+    pub normal: glm::IVec3,
+}
+
+/// Steps through the voxel grid along a ray (Amanatides & Woo),
+/// returning the first solid or entity block within the maximum distance.
+pub fn raycast(
+    world: &World,
+    origin: &glm::Vec3,
+    direction: &glm::Vec3,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    if direction.norm_squared() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    let mut position = block_position_of(origin);
+    let mut step = glm::IVec3::zeros();
+    let mut t_max = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut t_delta = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            t_delta[axis] = 1.0 / direction[axis];
+            t_max[axis] = (position[axis] as f32 + 1.0 - origin[axis]) * t_delta[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            t_delta[axis] = -1.0 / direction[axis];
+            t_max[axis] = (origin[axis] - position[axis] as f32) * t_delta[axis];
+        }
+    }
+
+    let mut normal = glm::IVec3::zeros();
+    loop {
+        if let Some(block) = world.block_at(&position) {
+            let configuration = world.registry.configuration(block);
+            if configuration.is_solid || configuration.is_entity {
+                return Some(RaycastHit {
+                    block,
+                    position,
+                    normal,
+                });
+            }
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+
+        if t_max[axis] > max_distance {
+            return None;
+        }
+
+        position[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = glm::IVec3::zeros();
+        normal[axis] = -step[axis];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        terrain::{FlatTerrainGenerator, TerrainGenerator},
+        world::{test_world, ChunkPosition},
+    };
+
+    /// Dirt up to y = 3 in the given chunks
+    fn flat_world(positions: &[ChunkPosition]) -> World {
+        let generator = FlatTerrainGenerator { height: 4 };
+        let mut world = test_world(FlatTerrainGenerator { height: 4 });
+        for position in positions.iter() {
+            world.insert_chunk(*position, generator.generate(position));
+        }
+        world
+    }
+
+    #[test]
+    fn hits_the_ground_straight_down() {
+        let world = flat_world(&[glm::vec2(0, 0)]);
+        let hit = raycast(
+            &world,
+            &glm::vec3(8.5, 10.5, 8.5),
+            &glm::vec3(0.0, -1.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(hit.block, Block::Dirt);
+        assert_eq!(hit.position, glm::vec3(8, 3, 8));
+        assert_eq!(hit.normal, glm::vec3(0, 1, 0));
+    }
+
+    #[test]
+    fn hits_at_negative_coordinates() {
+        let world = flat_world(&[glm::vec2(-1, -1)]);
+        let hit = raycast(
+            &world,
+            &glm::vec3(-3.5, 5.5, -5.5),
+            &glm::vec3(0.0, -1.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(hit.position, glm::vec3(-4, 3, -6));
+        assert_eq!(hit.normal, glm::vec3(0, 1, 0));
+    }
+
+    #[test]
+    fn misses_past_the_maximum_distance() {
+        let world = flat_world(&[glm::vec2(0, 0)]);
+        let origin = glm::vec3(8.5, 10.5, 8.5);
+        let down = glm::vec3(0.0, -1.0, 0.0);
+        assert!(raycast(&world, &origin, &down, 5.0).is_none());
+        assert!(raycast(&world, &origin, &down, 7.0).is_some());
+    }
+
+    #[test]
+    fn starting_inside_a_block_hits_it_without_a_normal() {
+        let world = flat_world(&[glm::vec2(0, 0)]);
+        let hit = raycast(
+            &world,
+            &glm::vec3(8.5, 2.5, 8.5),
+            &glm::vec3(1.0, 0.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(hit.position, glm::vec3(8, 2, 8));
+        assert_eq!(hit.normal, glm::IVec3::zeros());
+    }
+
+    #[test]
+    fn passes_through_air_and_water() {
+        let mut world = flat_world(&[glm::vec2(0, 0)]);
+        world.set_block(&glm::vec3(8, 4, 8), Block::Water(0));
+        let hit = raycast(
+            &world,
+            &glm::vec3(8.5, 10.5, 8.5),
+            &glm::vec3(0.0, -1.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(hit.position, glm::vec3(8, 3, 8));
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, fs};
+
+use crate::{
+    block::{Block, BlockConfiguration, RenderLayer},
+    light::MAX_LIGHT,
+    texture_pack::TexturePack,
+};
+
+/// Shorthand forms for a block's faces, mirroring the `BlockConfiguration` constructors.
+/// Tiles are named in the default texture pack's manifest.
+#[derive(Debug, Clone, Deserialize)]
+pub enum Faces {
+    Empty,
+    Single(String),
+    SameSides {
+        sides: String,
+        top: String,
+        bottom: String,
+    },
+    Entity(String),
+    Each {
+        left: String,
+        right: String,
+        front: String,
+        back: String,
+        top: String,
+        bottom: String,
+    },
+}
+
+impl Faces {
+    /// Looks up the tiles by name, failing on the first name the pack doesn't have
+    pub fn configuration(&self, tiles: &TexturePack) -> Result<BlockConfiguration> {
+        let tile = |name: &str| match tiles.tile_index(name) {
+            Some(index) => Ok(index as i32),
+            None => bail!(
+                "Tile '{}' isn't named in texture pack '{}'",
+                name,
+                tiles.name
+            ),
+        };
+        let configuration = match self {
+            Faces::Empty => BlockConfiguration::empty(),
+            Faces::Single(name) => BlockConfiguration::new_single(tile(name)?),
+            Faces::SameSides { sides, top, bottom } => {
+                BlockConfiguration::new_same_sides(tile(sides)?, tile(top)?, tile(bottom)?)
+            }
+            Faces::Entity(name) => BlockConfiguration::new_entity(tile(name)?),
+            Faces::Each {
+                left,
+                right,
+                front,
+                back,
+                top,
+                bottom,
+            } => BlockConfiguration::new(
+                tile(left)?,
+                tile(right)?,
+                tile(front)?,
+                tile(back)?,
+                tile(top)?,
+                tile(bottom)?,
+            ),
+        };
+        Ok(configuration)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockDefinition {
+    pub name: String,
+    /// Required for new blocks. Built-in blocks keep their default faces without it.
+    #[serde(default)]
+    pub faces: Option<Faces>,
+    #[serde(default)]
+    pub is_solid: Option<bool>,
+    #[serde(default)]
+    pub is_entity: Option<bool>,
+    #[serde(default)]
+    pub light_emission: Option<u8>,
+    #[serde(default)]
+    pub render_layer: Option<RenderLayer>,
+}
+
+impl BlockDefinition {
+    /// The configuration of a block that only the definition describes
+    pub fn configuration(&self, tiles: &TexturePack) -> Result<BlockConfiguration> {
+        let faces = self
+            .faces
+            .as_ref()
+            .with_context(|| format!("Block '{}' is new, so it needs faces", self.name))?;
+        let mut configuration = faces
+            .configuration(tiles)
+            .with_context(|| format!("Failed to find the tiles of block '{}'", self.name))?;
+        self.apply_flags(&mut configuration);
+        Ok(configuration)
+    }
+
+    /// Changes what the definition sets in an existing configuration, leaving the rest
+    pub fn apply(&self, configuration: &mut BlockConfiguration, tiles: &TexturePack) -> Result<()> {
+        if let Some(faces) = self.faces.as_ref() {
+            let faces = faces
+                .configuration(tiles)
+                .with_context(|| format!("Failed to find the tiles of block '{}'", self.name))?;
+            configuration.left = faces.left;
+            configuration.right = faces.right;
+            configuration.front = faces.front;
+            configuration.back = faces.back;
+            configuration.top = faces.top;
+            configuration.bottom = faces.bottom;
+        }
+        self.apply_flags(configuration);
+        Ok(())
+    }
+
+    fn apply_flags(&self, configuration: &mut BlockConfiguration) {
+        if let Some(is_solid) = self.is_solid {
+            configuration.is_solid = is_solid;
+        }
+        if let Some(is_entity) = self.is_entity {
+            configuration.is_entity = is_entity;
+        }
+        if let Some(light_emission) = self.light_emission {
+            configuration.light_emission = light_emission;
+        }
+        if let Some(render_layer) = self.render_layer {
+            configuration.render_layer = render_layer;
+        }
+    }
+}
+
+/// Maps every known block to its configuration.
+/// Built-in blocks start out with their default configurations, which a registry
+/// file can change by name. A fluid source's name changes every level of the fluid.
+/// Unknown names in the file become custom blocks.
+pub struct BlockRegistry {
+    configurations: HashMap<Block, BlockConfiguration>,
+    blocks_by_name: HashMap<String, Block>,
+    names: HashMap<Block, String>,
+    empty: BlockConfiguration,
+}
+
+impl BlockRegistry {
+    /// Creates a registry of just the built-in blocks with their default faces
+    pub fn built_in(tiles: &TexturePack) -> Result<Self> {
+        let mut registry = Self {
+            configurations: HashMap::new(),
+            blocks_by_name: HashMap::new(),
+            names: HashMap::new(),
+            empty: BlockConfiguration::empty(),
+        };
+        for block in Block::all_built_in() {
+            if let Some(name) = block.built_in_name() {
+                let configuration = block
+                    .default_configuration(tiles)
+                    .with_context(|| format!("Failed to find the tiles of block '{}'", name))?;
+                registry.register(block, name, configuration);
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Loads block definitions from a RON file, looking up their tiles in a texture pack
+    pub fn load(path: &str, tiles: &TexturePack) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read block registry '{}'", path))?;
+        let definitions: Vec<BlockDefinition> = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse block registry '{}'", path))?;
+        Self::from_definitions(&definitions, tiles)
+            .with_context(|| format!("Failed to load block registry '{}'", path))
+    }
+
+    pub fn from_definitions(definitions: &[BlockDefinition], tiles: &TexturePack) -> Result<Self> {
+        let mut registry = Self::built_in(tiles)?;
+        let mut next_custom_id: u16 = 0;
+        for definition in definitions.iter() {
+            if definition.light_emission.unwrap_or(0) > MAX_LIGHT {
+                bail!(
+                    "Block '{}' emits light level {}, the maximum is {}",
+                    definition.name,
+                    definition.light_emission.unwrap_or(0),
+                    MAX_LIGHT
+                );
+            }
+            if let Some(block) = registry.block_named(&definition.name) {
+                let blocks = match block.fluid() {
+                    Some((fluid, 0)) => (0..=fluid.max_level())
+                        .map(|level| fluid.block(level))
+                        .collect(),
+                    _ => vec![block],
+                };
+                for block in blocks {
+                    let mut configuration = registry.configuration(block).clone();
+                    definition.apply(&mut configuration, tiles)?;
+                    registry.configurations.insert(block, configuration);
+                }
+                continue;
+            }
+            if next_custom_id > u8::MAX as u16 {
+                bail!(
+                    "Too many custom blocks, '{}' exceeds the limit of {}",
+                    definition.name,
+                    u8::MAX as u16 + 1
+                );
+            }
+            let block = Block::Custom(next_custom_id as u8);
+            next_custom_id += 1;
+            registry.register(block, &definition.name, definition.configuration(tiles)?);
+        }
+        Ok(registry)
+    }
+
+    /// Returns the configuration of a block, or an empty configuration if it isn't registered
+    pub fn configuration(&self, block: Block) -> &BlockConfiguration {
+        self.configurations.get(&block).unwrap_or(&self.empty)
+    }
+
+    pub fn block_named(&self, name: &str) -> Option<Block> {
+        self.blocks_by_name.get(name).copied()
+    }
+
+    pub fn name(&self, block: Block) -> Option<&str> {
+        self.names.get(&block).map(String::as_str)
+    }
+
+    fn register(&mut self, block: Block, name: &str, configuration: BlockConfiguration) {
+        self.configurations.insert(block, configuration);
+        self.blocks_by_name.insert(name.to_string(), block);
+        self.names.insert(block, name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture_pack::DEFAULT_PACK;
+
+    fn tiles() -> TexturePack {
+        TexturePack::load(DEFAULT_PACK).unwrap()
+    }
+
+    fn registry(source: &str) -> Result<BlockRegistry> {
+        let definitions: Vec<BlockDefinition> =
+            ron::from_str(&format!("#![enable(implicit_some)]\n{}", source)).unwrap();
+        BlockRegistry::from_definitions(&definitions, &tiles())
+    }
+
+    fn error_message<T>(result: Result<T>) -> String {
+        match result {
+            Ok(_) => panic!("Expected an error"),
+            Err(error) => format!("{:#}", error),
+        }
+    }
+
+    fn tiles_of(configuration: &BlockConfiguration) -> [i32; 6] {
+        [
+            configuration.left,
+            configuration.right,
+            configuration.front,
+            configuration.back,
+            configuration.top,
+            configuration.bottom,
+        ]
+    }
+
+    #[test]
+    fn unknown_tiles_are_rejected() {
+        let error = error_message(registry(r#"[(name: "marble", faces: Single("marble"))]"#));
+        assert!(
+            error.contains("Tile 'marble' isn't named in texture pack 'Default'"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn shorthands_fill_in_the_faces() {
+        let registry = registry(
+            r#"[
+                (name: "a", faces: Single("dirt")),
+                (name: "b", faces: SameSides(sides: "tnt_side", top: "tnt_top", bottom: "tnt_bottom")),
+                (name: "c", faces: Each(
+                    left: "gravel", right: "grass", front: "dirt",
+                    back: "cobblestone", top: "bedrock", bottom: "oak_planks",
+                )),
+                (name: "d", faces: Entity("rose")),
+            ]"#,
+        )
+        .unwrap();
+        let tiles = tiles();
+        let tile = |name: &str| tiles.tile_index(name).unwrap() as i32;
+        let configuration =
+            |name: &str| registry.configuration(registry.block_named(name).unwrap());
+
+        assert_eq!(tiles_of(configuration("a")), [tile("dirt"); 6]);
+        let (side, top, bottom) = (tile("tnt_side"), tile("tnt_top"), tile("tnt_bottom"));
+        assert_eq!(
+            tiles_of(configuration("b")),
+            [side, side, side, side, top, bottom]
+        );
+        let names = [
+            "gravel",
+            "grass",
+            "dirt",
+            "cobblestone",
+            "bedrock",
+            "oak_planks",
+        ];
+        assert_eq!(
+            tiles_of(configuration("c")).to_vec(),
+            names.iter().map(|name| tile(name)).collect::<Vec<_>>()
+        );
+        for name in ["a", "b", "c"].iter() {
+            assert!(configuration(name).is_solid && !configuration(name).is_entity);
+        }
+        let entity = configuration("d");
+        assert!(entity.is_entity);
+        assert_eq!(entity.front, tile("rose"));
+        assert_eq!(entity.render_layer, RenderLayer::Cutout);
+    }
+
+    #[test]
+    fn new_blocks_get_custom_ids_in_order() {
+        let registry = registry(
+            r#"[
+                (name: "marble", faces: Single("gravel")),
+                (name: "dirt", faces: Single("gravel")),
+                (name: "slate", faces: Single("cobblestone")),
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(registry.block_named("marble"), Some(Block::Custom(0)));
+        assert_eq!(registry.block_named("slate"), Some(Block::Custom(1)));
+        assert_eq!(registry.block_named("dirt"), Some(Block::Dirt));
+        assert_eq!(registry.name(Block::Custom(1)), Some("slate"));
+        assert_eq!(registry.name(Block::Custom(2)), None);
+    }
+
+    #[test]
+    fn new_blocks_need_faces() {
+        let error = error_message(registry(r#"[(name: "marble", is_solid: false)]"#));
+        assert!(
+            error.contains("'marble' is new, so it needs faces"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn light_emission_is_capped() {
+        let source = r#"[(name: "lamp", faces: Single("dirt"), light_emission: 16)]"#;
+        let error = error_message(registry(source));
+        assert!(error.contains("emits light level 16"), "{}", error);
+        let source = r#"[(name: "lamp", faces: Single("dirt"), light_emission: 15)]"#;
+        let registry = registry(source).unwrap();
+        let lamp = registry.block_named("lamp").unwrap();
+        assert_eq!(registry.configuration(lamp).light_emission, MAX_LIGHT);
+    }
+
+    #[test]
+    fn overrides_only_change_what_they_set() {
+        let tiles = tiles();
+        let built_in = BlockRegistry::built_in(&tiles).unwrap();
+        let registry = registry(
+            r#"[
+                (name: "rose", faces: Entity("thistle")),
+                (name: "dirt", light_emission: 4),
+            ]"#,
+        )
+        .unwrap();
+
+        let rose = registry.configuration(Block::Rose);
+        assert!(rose.is_entity && rose.is_solid == built_in.configuration(Block::Rose).is_solid);
+        assert_eq!(rose.front, tiles.tile_index("thistle").unwrap() as i32);
+        let dirt = registry.configuration(Block::Dirt);
+        assert_eq!(dirt.light_emission, 4);
+        assert_eq!(
+            tiles_of(dirt),
+            tiles_of(built_in.configuration(Block::Dirt))
+        );
+    }
+
+    #[test]
+    fn fluid_overrides_apply_to_every_level() {
+        let built_in = BlockRegistry::built_in(&tiles()).unwrap();
+        let registry = registry(
+            r#"[
+                (name: "water", light_emission: 3),
+                (name: "flowing_water_2", light_emission: 5),
+            ]"#,
+        )
+        .unwrap();
+        for level in 0..=7 {
+            let water = registry.configuration(Block::Water(level));
+            let default = built_in.configuration(Block::Water(level));
+            assert!(!water.is_solid);
+            assert_eq!(water.render_layer, RenderLayer::Translucent);
+            assert_eq!(tiles_of(water), tiles_of(default));
+            let expected = if level == 2 { 5 } else { 3 };
+            assert_eq!(water.light_emission, expected, "level {}", level);
+        }
+        assert_eq!(
+            registry.configuration(Block::Lava(1)).light_emission,
+            MAX_LIGHT
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{bail, Context, Result};
+use glutin::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{
+        DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+        MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+    },
+    window::WindowId,
+};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::gamepad::{GamepadButton, GamepadState};
+
+const RECORDING_VERSION: u32 = 1;
+
+/// The window events that affect the app, in a form that can be written to a file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key { key: VirtualKeyCode, pressed: bool },
+    MouseButton { button: MouseButton, pressed: bool },
+    CursorMoved { x: f64, y: f64 },
+    MouseWheel { x: f32, y: f32 },
+    Resized { width: u32, height: u32 },
+}
+
+impl InputEvent {
+    pub fn from_event(event: &Event<()>) -> Option<Self> {
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return None,
+        };
+        let input_event = match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => InputEvent::Key {
+                key,
+                pressed: state == ElementState::Pressed,
+            },
+            WindowEvent::MouseInput { button, state, .. } => InputEvent::MouseButton {
+                button,
+                pressed: state == ElementState::Pressed,
+            },
+            WindowEvent::CursorMoved { position, .. } => InputEvent::CursorMoved {
+                x: position.x,
+                y: position.y,
+            },
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(x, y),
+                ..
+            } => InputEvent::MouseWheel { x, y },
+            WindowEvent::Resized(PhysicalSize { width, height }) => {
+                InputEvent::Resized { width, height }
+            }
+            _ => return None,
+        };
+        Some(input_event)
+    }
+
+    /// Recreates the window event, with placeholder window and device ids
+    #[allow(deprecated)]
+    pub fn to_event(&self) -> Event<'static, ()> {
+        // Safe as long as the ids are never handed back to glutin
+        let (window_id, device_id) = unsafe { (WindowId::dummy(), DeviceId::dummy()) };
+        let state = |pressed: bool| {
+            if pressed {
+                ElementState::Pressed
+            } else {
+                ElementState::Released
+            }
+        };
+        let event = match *self {
+            InputEvent::Key { key, pressed } => WindowEvent::KeyboardInput {
+                device_id,
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: state(pressed),
+                    virtual_keycode: Some(key),
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+            InputEvent::MouseButton { button, pressed } => WindowEvent::MouseInput {
+                device_id,
+                state: state(pressed),
+                button,
+                modifiers: ModifiersState::empty(),
+            },
+            InputEvent::CursorMoved { x, y } => WindowEvent::CursorMoved {
+                device_id,
+                position: PhysicalPosition::new(x, y),
+                modifiers: ModifiersState::empty(),
+            },
+            InputEvent::MouseWheel { x, y } => WindowEvent::MouseWheel {
+                device_id,
+                delta: MouseScrollDelta::LineDelta(x, y),
+                phase: TouchPhase::Moved,
+                modifiers: ModifiersState::empty(),
+            },
+            InputEvent::Resized { width, height } => {
+                WindowEvent::Resized(PhysicalSize::new(width, height))
+            }
+        };
+        Event::WindowEvent { window_id, event }
+    }
+}
+
+/// The gamepad state at the start of a frame's update
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GamepadSnapshot {
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub pressed_buttons: Vec<GamepadButton>,
+}
+
+impl GamepadSnapshot {
+    pub fn capture(state: &GamepadState) -> Self {
+        let mut pressed_buttons = state.pressed_buttons.iter().copied().collect::<Vec<_>>();
+        pressed_buttons.sort_by_key(|button| *button as u8);
+        Self {
+            left_stick: [state.left_stick.x, state.left_stick.y],
+            right_stick: [state.right_stick.x, state.right_stick.y],
+            pressed_buttons,
+        }
+    }
+
+    /// Moves a gamepad state to this snapshot, as if its events had just been read
+    pub fn restore(&self, state: &mut GamepadState) {
+        state.new_frame();
+        state.left_stick = glm::vec2(self.left_stick[0], self.left_stick[1]);
+        state.right_stick = glm::vec2(self.right_stick[0], self.right_stick[1]);
+        let released = state
+            .pressed_buttons
+            .iter()
+            .filter(|button| !self.pressed_buttons.contains(button))
+            .copied()
+            .collect::<Vec<_>>();
+        for button in released {
+            state.set_button(button, false);
+        }
+        for button in self.pressed_buttons.iter() {
+            state.set_button(*button, true);
+        }
+    }
+}
+
+/// Everything the app received between two updates
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    /// Seconds since the previous frame, as measured by `System`
+    pub delta_time: f64,
+    pub events: Vec<InputEvent>,
+    pub gamepad: GamepadSnapshot,
+}
+
+/// A recorded session, starting from a newly generated world
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub seed: u64,
+    pub window_dimensions: [u32; 2],
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    pub fn new(seed: u64, window_dimensions: [u32; 2]) -> Self {
+        Self {
+            version: RECORDING_VERSION,
+            seed,
+            window_dimensions,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording '{}'", path))?;
+        let recording: Recording = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse recording '{}'", path))?;
+        if recording.version != RECORDING_VERSION {
+            bail!(
+                "Recording '{}' has version {}, expected {}",
+                path,
+                recording.version,
+                RECORDING_VERSION
+            );
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let source = ron::to_string(self).context("Failed to serialize recording")?;
+        fs::write(path, source).with_context(|| format!("Failed to write recording '{}'", path))
+    }
+}
+
+/// Collects the events and frame times the app receives into a recording
+pub struct Recorder {
+    pub path: String,
+    pub recording: Recording,
+    pending_events: Vec<InputEvent>,
+}
+
+impl Recorder {
+    pub fn new(path: &str, seed: u64, window_dimensions: [u32; 2]) -> Self {
+        Self {
+            path: path.to_string(),
+            recording: Recording::new(seed, window_dimensions),
+            pending_events: Vec::new(),
+        }
+    }
+
+    pub fn record_event(&mut self, event: &Event<()>) {
+        if let Some(event) = InputEvent::from_event(event) {
+            self.pending_events.push(event);
+        }
+    }
+
+    /// Ends the current frame, called at the start of each update
+    pub fn finish_frame(&mut self, delta_time: f64, gamepad: &GamepadState) {
+        self.recording.frames.push(Frame {
+            delta_time,
+            events: std::mem::take(&mut self.pending_events),
+            gamepad: GamepadSnapshot::capture(gamepad),
+        });
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.recording.save(&self.path)
+    }
+}
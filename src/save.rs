@@ -0,0 +1,911 @@
+use anyhow::{bail, ensure, Context, Result};
+use nalgebra_glm as glm;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    block::Block,
+    inventory::{GameMode, Inventory, ItemStack, HOTBAR_SLOTS, MAX_STACK_SIZE},
+    registry::BlockRegistry,
+    terrain::NoiseTerrainGenerator,
+    world::{Chunk, ChunkPosition, ChunkStorage, World, CHUNK_DEPTH, CHUNK_LENGTH, CHUNK_WIDTH},
+};
+
+const WORLD_MAGIC: &[u8; 4] = b"NMCW";
+const REGION_MAGIC: &[u8; 4] = b"NMCR";
+const INVENTORY_MAGIC: &[u8; 4] = b"NMCI";
+const FORMAT_VERSION: u32 = 1;
+const WORLD_FILE: &str = "world.dat";
+const INVENTORY_FILE: &str = "inventory.dat";
+const REGION_EXTENSION: &str = "region";
+
+/// The width and length of a region, in chunks
+pub const REGION_SIZE: i32 = 8;
+
+const CHUNK_VOLUME: usize = CHUNK_WIDTH * CHUNK_LENGTH * CHUNK_DEPTH;
+
+/// Region coordinates along the world's x and z axes, in units of regions
+pub type RegionPosition = glm::IVec2;
+
+pub fn region_position_of(chunk_position: &ChunkPosition) -> RegionPosition {
+    glm::vec2(
+        chunk_position.x.div_euclid(REGION_SIZE),
+        chunk_position.y.div_euclid(REGION_SIZE),
+    )
+}
+
+pub fn world_exists(directory: &Path) -> bool {
+    directory.join(WORLD_FILE).is_file()
+}
+
+/// Writes the world header and the chunks edited since they were generated or loaded.
+/// The chunks are merged into an earlier save of the same world in the directory,
+/// and replace any other world saved there.
+///
+/// Layout:
+///   world.dat         header with the format version, seed and chunk dimensions
+///   r.<x>.<z>.region  the chunks within one region
+pub fn save_world(world: &World, directory: &Path) -> Result<()> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create save directory {:?}", directory))?;
+    let header_path = directory.join(WORLD_FILE);
+    let same_world = fs::read(&header_path)
+        .ok()
+        .and_then(|header| decode_header(&header).ok())
+        == Some(world.seed);
+
+    let chunks = world
+        .all_chunks()
+        .filter(|(_, chunk)| chunk.modified)
+        .map(|(position, chunk)| (*position, chunk))
+        .collect::<Vec<_>>();
+    // Every region is written before any stale one is removed,
+    // so a failed save leaves the previous one readable
+    let written = write_regions(directory, &chunks, &world.registry, same_world)?;
+    write_file(&header_path, &encode_header(world.seed))?;
+
+    if !same_world {
+        for path in region_paths(directory)? {
+            if !written.contains(&path) {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove stale region {:?}", path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens a world saved with `save_world`. Its chunks stay in the region files
+/// until the world streams them in.
+pub fn load_world(directory: &Path, registry: Arc<BlockRegistry>) -> Result<World> {
+    let header_path = directory.join(WORLD_FILE);
+    let header = fs::read(&header_path)
+        .with_context(|| format!("Failed to read world header {:?}", header_path))?;
+    let seed = decode_header(&header)
+        .with_context(|| format!("Invalid world header {:?}", header_path))?;
+
+    let storage = RegionStorage {
+        directory: directory.to_path_buf(),
+        registry: registry.clone(),
+    };
+    Ok(World::with_storage(
+        seed,
+        registry,
+        Arc::new(NoiseTerrainGenerator::new(seed)),
+        Arc::new(storage),
+    ))
+}
+
+/// Starts a new world saved in a directory, so chunks it unloads can be written there
+/// before the rest of the world is saved. Region files left without a world header are removed.
+pub fn new_world(directory: &Path, seed: u64, registry: Arc<BlockRegistry>) -> Result<World> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create save directory {:?}", directory))?;
+    for path in region_paths(directory)? {
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale region {:?}", path))?;
+    }
+    write_file(&directory.join(WORLD_FILE), &encode_header(seed))?;
+    load_world(directory, registry)
+}
+
+/// The chunks of a saved world, read from its region files one at a time
+/// and written back into them
+pub struct RegionStorage {
+    directory: PathBuf,
+    registry: Arc<BlockRegistry>,
+}
+
+impl ChunkStorage for RegionStorage {
+    fn load(&self, position: &ChunkPosition) -> Result<Option<Chunk>> {
+        let path = region_path(&self.directory, &region_position_of(position));
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path).with_context(|| format!("Failed to read region {:?}", path))?;
+        let chunks = decode_region(&bytes, &self.registry)
+            .with_context(|| format!("Invalid region file {:?}", path))?;
+        Ok(chunks
+            .into_iter()
+            .find(|(chunk_position, _)| chunk_position == position)
+            .map(|(_, chunk)| chunk))
+    }
+
+    fn store(&self, chunks: &[(ChunkPosition, &Chunk)]) -> Result<()> {
+        write_regions(&self.directory, chunks, &self.registry, true)?;
+        Ok(())
+    }
+}
+
+/// Writes the player's inventory next to a saved world
+pub fn save_inventory(
+    inventory: &Inventory,
+    registry: &BlockRegistry,
+    directory: &Path,
+) -> Result<()> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("Failed to create save directory {:?}", directory))?;
+    let bytes = encode_inventory(inventory, registry)?;
+    write_file(&directory.join(INVENTORY_FILE), &bytes)
+}
+
+/// Loads an inventory saved with `save_inventory`, if the save has one
+pub fn load_inventory(directory: &Path, registry: &BlockRegistry) -> Result<Option<Inventory>> {
+    let path = directory.join(INVENTORY_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let bytes = fs::read(&path).with_context(|| format!("Failed to read inventory {:?}", path))?;
+    let inventory = decode_inventory(&bytes, registry)
+        .with_context(|| format!("Invalid inventory file {:?}", path))?;
+    Ok(Some(inventory))
+}
+
+/// The region files in a save directory, sorted by name
+fn region_paths(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let entries = fs::read_dir(directory)
+        .with_context(|| format!("Failed to read save directory {:?}", directory))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) == Some(REGION_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Writes chunks into the region files holding them, returning the paths written.
+/// Merging keeps the other chunks already saved in those regions.
+fn write_regions(
+    directory: &Path,
+    chunks: &[(ChunkPosition, &Chunk)],
+    registry: &BlockRegistry,
+    merge: bool,
+) -> Result<HashSet<PathBuf>> {
+    let mut regions: HashMap<RegionPosition, Vec<(ChunkPosition, &Chunk)>> = HashMap::new();
+    for (position, chunk) in chunks.iter() {
+        regions
+            .entry(region_position_of(position))
+            .or_default()
+            .push((*position, *chunk));
+    }
+
+    let mut written = HashSet::new();
+    for (region_position, chunks) in regions.iter() {
+        let path = region_path(directory, region_position);
+        let saved = if merge && path.is_file() {
+            let bytes =
+                fs::read(&path).with_context(|| format!("Failed to read region {:?}", path))?;
+            decode_region(&bytes, registry)
+                .with_context(|| format!("Invalid region file {:?}", path))?
+        } else {
+            Vec::new()
+        };
+        let mut merged = chunks.clone();
+        for (position, chunk) in saved.iter() {
+            if !chunks
+                .iter()
+                .any(|(replacement, _)| replacement == position)
+            {
+                merged.push((*position, chunk));
+            }
+        }
+        merged.sort_by_key(|(position, _)| (position.y, position.x));
+        let bytes = encode_region(region_position, &merged, registry)?;
+        write_file(&path, &bytes)?;
+        written.insert(path);
+    }
+    Ok(written)
+}
+
+fn region_path(directory: &Path, position: &RegionPosition) -> PathBuf {
+    directory.join(format!(
+        "r.{}.{}.{}",
+        position.x, position.y, REGION_EXTENSION
+    ))
+}
+
+/// Writes through a temporary file so an interrupted save doesn't leave a partial file behind
+fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, bytes)
+        .with_context(|| format!("Failed to write {:?}", temporary_path))?;
+    fs::rename(&temporary_path, path).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(())
+}
+
+pub fn encode_header(seed: u64) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.bytes(WORLD_MAGIC);
+    writer.u32(FORMAT_VERSION);
+    writer.u64(seed);
+    writer.u16(CHUNK_WIDTH as u16);
+    writer.u16(CHUNK_LENGTH as u16);
+    writer.u16(CHUNK_DEPTH as u16);
+    writer.finish()
+}
+
+/// Validates a world header and returns the world's seed
+pub fn decode_header(bytes: &[u8]) -> Result<u64> {
+    let mut reader = Reader::new(bytes)?;
+    reader.magic(WORLD_MAGIC)?;
+    reader.version()?;
+    let seed = reader.u64("seed")?;
+    reader.chunk_dimensions()?;
+    reader.finish()?;
+    Ok(seed)
+}
+
+/// Encodes the chunks of one region.
+/// Each chunk stores a palette of block names followed by run-length encoded palette indices.
+pub fn encode_region(
+    region_position: &RegionPosition,
+    chunks: &[(ChunkPosition, &Chunk)],
+    registry: &BlockRegistry,
+) -> Result<Vec<u8>> {
+    let mut writer = Writer::default();
+    writer.bytes(REGION_MAGIC);
+    writer.u32(FORMAT_VERSION);
+    writer.i32(region_position.x);
+    writer.i32(region_position.y);
+    writer.u32(chunks.len() as u32);
+
+    for (position, chunk) in chunks.iter() {
+        ensure!(
+            region_position_of(position) == *region_position,
+            "Chunk ({}, {}) doesn't belong in region ({}, {})",
+            position.x,
+            position.y,
+            region_position.x,
+            region_position.y
+        );
+
+        let mut palette: Vec<Block> = Vec::new();
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for block in chunk.blocks.iter().flatten().flatten() {
+            let index = match palette.iter().position(|entry| entry == block) {
+                Some(index) => index,
+                None => {
+                    palette.push(*block);
+                    palette.len() - 1
+                }
+            } as u16;
+            match runs.last_mut() {
+                Some((last_index, length)) if *last_index == index && *length < u16::MAX => {
+                    *length += 1
+                }
+                _ => runs.push((index, 1)),
+            }
+        }
+
+        writer.i32(position.x);
+        writer.i32(position.y);
+        writer.u16(palette.len() as u16);
+        for block in palette.iter() {
+            let name = registry
+                .name(*block)
+                .with_context(|| format!("Block {:?} has no registered name", block))?;
+            writer.u16(name.len() as u16);
+            writer.bytes(name.as_bytes());
+        }
+        writer.u32(runs.len() as u32);
+        for (index, length) in runs {
+            writer.u16(index);
+            writer.u16(length);
+        }
+    }
+
+    Ok(writer.finish())
+}
+
+/// Encodes the game mode, the selected slot and each slot's count,
+/// followed by the block's name for slots that aren't empty
+pub fn encode_inventory(inventory: &Inventory, registry: &BlockRegistry) -> Result<Vec<u8>> {
+    let mut writer = Writer::default();
+    writer.bytes(INVENTORY_MAGIC);
+    writer.u32(FORMAT_VERSION);
+    writer.u8(match inventory.game_mode {
+        GameMode::Creative => 0,
+        GameMode::Survival => 1,
+    });
+    writer.u8(inventory.selected() as u8);
+    writer.u8(HOTBAR_SLOTS as u8);
+    for slot in inventory.slots().iter() {
+        match slot {
+            Some(stack) => {
+                let name = registry
+                    .name(stack.block)
+                    .with_context(|| format!("Block {:?} has no registered name", stack.block))?;
+                writer.u32(stack.count);
+                writer.u16(name.len() as u16);
+                writer.bytes(name.as_bytes());
+            }
+            None => writer.u32(0),
+        }
+    }
+    Ok(writer.finish())
+}
+
+pub fn decode_inventory(bytes: &[u8], registry: &BlockRegistry) -> Result<Inventory> {
+    let mut reader = Reader::new(bytes)?;
+    reader.magic(INVENTORY_MAGIC)?;
+    reader.version()?;
+    let game_mode = match reader.u8("game mode")? {
+        0 => GameMode::Creative,
+        1 => GameMode::Survival,
+        other => bail!("Unknown game mode {}", other),
+    };
+    let mut inventory = Inventory::new(game_mode);
+
+    let selected = reader.u8("selected slot")? as usize;
+    let number_of_slots = reader.u8("slot count")? as usize;
+    ensure!(
+        number_of_slots == HOTBAR_SLOTS,
+        "Inventory has {} slots, expected {}",
+        number_of_slots,
+        HOTBAR_SLOTS
+    );
+    ensure!(
+        selected < HOTBAR_SLOTS,
+        "Selected slot {} is out of range",
+        selected
+    );
+    inventory.select(selected);
+
+    for slot in 0..number_of_slots {
+        let count = reader.u32("stack count")?;
+        if count == 0 {
+            continue;
+        }
+        ensure!(
+            count <= MAX_STACK_SIZE,
+            "Slot {} holds {} blocks, more than the stack size of {}",
+            slot,
+            count,
+            MAX_STACK_SIZE
+        );
+        let name_length = reader.u16("block name length")?;
+        let name = std::str::from_utf8(reader.take(name_length as usize, "block name")?)
+            .context("Block name is not valid UTF-8")?;
+        let block = registry
+            .block_named(name)
+            .with_context(|| format!("Unknown block '{}' in slot {}", name, slot))?;
+        inventory.set_slot(slot, Some(ItemStack { block, count }));
+    }
+
+    reader.finish()?;
+    Ok(inventory)
+}
+
+pub fn decode_region(
+    bytes: &[u8],
+    registry: &BlockRegistry,
+) -> Result<Vec<(ChunkPosition, Chunk)>> {
+    let mut reader = Reader::new(bytes)?;
+    reader.magic(REGION_MAGIC)?;
+    reader.version()?;
+    let region_position = glm::vec2(reader.i32("region x")?, reader.i32("region z")?);
+    let number_of_chunks = reader.u32("chunk count")?;
+
+    let mut chunks = Vec::new();
+    for chunk_index in 0..number_of_chunks {
+        let position = glm::vec2(reader.i32("chunk x")?, reader.i32("chunk z")?);
+        ensure!(
+            region_position_of(&position) == region_position,
+            "Chunk {} at ({}, {}) lies outside region ({}, {})",
+            chunk_index,
+            position.x,
+            position.y,
+            region_position.x,
+            region_position.y
+        );
+
+        let palette_length = reader.u16("palette length")?;
+        let mut palette = Vec::with_capacity(palette_length as usize);
+        for _ in 0..palette_length {
+            let name_length = reader.u16("block name length")?;
+            let name = std::str::from_utf8(reader.take(name_length as usize, "block name")?)
+                .context("Block name is not valid UTF-8")?;
+            let block = registry
+                .block_named(name)
+                .with_context(|| format!("Unknown block '{}' in palette", name))?;
+            palette.push(block);
+        }
+
+        let mut blocks = Vec::with_capacity(CHUNK_VOLUME);
+        let number_of_runs = reader.u32("run count")?;
+        for _ in 0..number_of_runs {
+            let index = reader.u16("run palette index")? as usize;
+            let length = reader.u16("run length")? as usize;
+            let block = *palette.get(index).with_context(|| {
+                format!(
+                    "Palette index {} is out of range for a palette of {} blocks",
+                    index,
+                    palette.len()
+                )
+            })?;
+            ensure!(
+                blocks.len() + length <= CHUNK_VOLUME,
+                "Chunk ({}, {}) has more than {} blocks",
+                position.x,
+                position.y,
+                CHUNK_VOLUME
+            );
+            // repeat_n would need Rust 1.82
+            #[allow(clippy::manual_repeat_n)]
+            blocks.extend(std::iter::repeat(block).take(length));
+        }
+        ensure!(
+            blocks.len() == CHUNK_VOLUME,
+            "Chunk ({}, {}) has {} blocks, expected {}",
+            position.x,
+            position.y,
+            blocks.len(),
+            CHUNK_VOLUME
+        );
+
+        let mut chunk = Chunk::new(&position);
+        for (destination, block) in chunk.blocks.iter_mut().flatten().flatten().zip(blocks) {
+            *destination = block;
+        }
+        chunks.push((position, chunk));
+    }
+
+    reader.finish()?;
+    Ok(chunks)
+}
+
+/// Appends little-endian values, finishing with a CRC-32 of everything written
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let checksum = crc32(&self.bytes);
+        self.u32(checksum);
+        self.bytes
+    }
+}
+
+/// Reads little-endian values, reporting what was being read when the data runs out
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Verifies the trailing checksum before any of the contents are read
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= 4,
+            "File is truncated, it is only {} bytes long",
+            bytes.len()
+        );
+        let (contents, checksum) = bytes.split_at(bytes.len() - 4);
+        let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        let actual = crc32(contents);
+        ensure!(
+            expected == actual,
+            "Checksum mismatch (stored {:08x}, computed {:08x}), the file is corrupt or truncated",
+            expected,
+            actual
+        );
+        Ok(Self {
+            bytes: contents,
+            offset: 0,
+        })
+    }
+
+    fn take(&mut self, count: usize, what: &str) -> Result<&'a [u8]> {
+        let end = self.offset + count;
+        if end > self.bytes.len() {
+            bail!(
+                "Unexpected end of data reading {} at offset {}, needed {} bytes but {} remain",
+                what,
+                self.offset,
+                count,
+                self.bytes.len() - self.offset
+            );
+        }
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self, what: &str) -> Result<u8> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &str) -> Result<u16> {
+        let bytes = self.take(2, what)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self, what: &str) -> Result<u32> {
+        let bytes = self.take(4, what)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn i32(&mut self, what: &str) -> Result<i32> {
+        Ok(self.u32(what)? as i32)
+    }
+
+    fn u64(&mut self, what: &str) -> Result<u64> {
+        let low = self.u32(what)? as u64;
+        let high = self.u32(what)? as u64;
+        Ok(high << 32 | low)
+    }
+
+    fn magic(&mut self, magic: &[u8; 4]) -> Result<()> {
+        let bytes = self.take(4, "magic number")?;
+        ensure!(
+            bytes == magic,
+            "Bad magic number {:?}, expected {:?}",
+            String::from_utf8_lossy(bytes),
+            String::from_utf8_lossy(magic)
+        );
+        Ok(())
+    }
+
+    fn version(&mut self) -> Result<()> {
+        let version = self.u32("format version")?;
+        ensure!(
+            version == FORMAT_VERSION,
+            "Unsupported format version {}, expected {}",
+            version,
+            FORMAT_VERSION
+        );
+        Ok(())
+    }
+
+    fn chunk_dimensions(&mut self) -> Result<()> {
+        let dimensions = [
+            self.u16("chunk width")?,
+            self.u16("chunk length")?,
+            self.u16("chunk depth")?,
+        ];
+        let expected = [CHUNK_WIDTH as u16, CHUNK_LENGTH as u16, CHUNK_DEPTH as u16];
+        ensure!(
+            dimensions == expected,
+            "Saved chunk dimensions {:?} don't match {:?}",
+            dimensions,
+            expected
+        );
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        ensure!(
+            self.offset == self.bytes.len(),
+            "{} unexpected trailing bytes",
+            self.bytes.len() - self.offset
+        );
+        Ok(())
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{terrain::FlatTerrainGenerator, world::test_world};
+
+    /// An empty directory of its own for each test
+    fn scratch_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("notminecraft-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn sample_chunk(position: &ChunkPosition) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for (index, block) in chunk.blocks.iter_mut().flatten().flatten().enumerate() {
+            *block = match index % 7 {
+                0 => Block::Dirt,
+                1 | 2 => Block::Cobblestone,
+                3 => Block::Water(3),
+                _ => Block::Air,
+            };
+        }
+        chunk.modified = true;
+        chunk
+    }
+
+    /// A region of one chunk with a hand-written palette and runs
+    fn handmade_region(palette: &[&str], runs: &[(u16, u16)]) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer.bytes(REGION_MAGIC);
+        writer.u32(FORMAT_VERSION);
+        writer.i32(0);
+        writer.i32(0);
+        writer.u32(1);
+        writer.i32(0);
+        writer.i32(0);
+        writer.u16(palette.len() as u16);
+        for name in palette.iter() {
+            writer.u16(name.len() as u16);
+            writer.bytes(name.as_bytes());
+        }
+        writer.u32(runs.len() as u32);
+        for (index, length) in runs.iter() {
+            writer.u16(*index);
+            writer.u16(*length);
+        }
+        writer.finish()
+    }
+
+    fn decode_error(bytes: &[u8], registry: &BlockRegistry) -> String {
+        match decode_region(bytes, registry) {
+            Ok(_) => panic!("The region decoded"),
+            Err(error) => format!("{:#}", error),
+        }
+    }
+
+    #[test]
+    fn regions_round_trip() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let positions = [glm::vec2(0, 0), glm::vec2(7, 3)];
+        let chunks = positions
+            .iter()
+            .map(|position| (*position, sample_chunk(position)))
+            .collect::<Vec<_>>();
+        let borrowed = chunks
+            .iter()
+            .map(|(position, chunk)| (*position, chunk))
+            .collect::<Vec<_>>();
+
+        let bytes = encode_region(&glm::vec2(0, 0), &borrowed, &world.registry).unwrap();
+        let decoded = decode_region(&bytes, &world.registry).unwrap();
+        assert_eq!(decoded.len(), 2);
+        for ((position, chunk), (decoded_position, decoded_chunk)) in
+            chunks.iter().zip(decoded.iter())
+        {
+            assert_eq!(position, decoded_position);
+            assert!(chunk.blocks == decoded_chunk.blocks);
+            assert!(!decoded_chunk.modified);
+        }
+    }
+
+    #[test]
+    fn chunks_must_belong_to_the_region() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let chunk = sample_chunk(&glm::vec2(8, 0));
+        let result = encode_region(
+            &glm::vec2(0, 0),
+            &[(glm::vec2(8, 0), &chunk)],
+            &world.registry,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn truncated_regions_are_rejected() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let chunk = sample_chunk(&glm::vec2(0, 0));
+        let bytes = encode_region(
+            &glm::vec2(0, 0),
+            &[(glm::vec2(0, 0), &chunk)],
+            &world.registry,
+        )
+        .unwrap();
+
+        let error = decode_error(&bytes[..bytes.len() - 10], &world.registry);
+        assert!(error.contains("Checksum mismatch"), "{}", error);
+        let error = decode_error(&bytes[..2], &world.registry);
+        assert!(error.contains("truncated"), "{}", error);
+
+        // A valid checksum over data that stops partway through a chunk
+        let mut writer = Writer::default();
+        writer.bytes(&bytes[..40]);
+        let error = decode_error(&writer.finish(), &world.registry);
+        assert!(error.contains("Unexpected end of data"), "{}", error);
+    }
+
+    #[test]
+    fn bad_palette_indices_are_rejected() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let bytes = handmade_region(&["dirt"], &[(0, 100), (5, 100)]);
+        let error = decode_error(&bytes, &world.registry);
+        assert!(
+            error.contains("Palette index 5 is out of range"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_block_names_are_rejected() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let bytes = handmade_region(&["dirt", "unobtainium"], &[(0, CHUNK_VOLUME as u16)]);
+        let error = decode_error(&bytes, &world.registry);
+        assert!(error.contains("Unknown block 'unobtainium'"), "{}", error);
+    }
+
+    #[test]
+    fn chunks_with_missing_blocks_are_rejected() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let bytes = handmade_region(&["dirt"], &[(0, 100)]);
+        let error = decode_error(&bytes, &world.registry);
+        assert!(error.contains("has 100 blocks"), "{}", error);
+    }
+
+    #[test]
+    fn inventories_round_trip() {
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        let mut inventory = Inventory::new(GameMode::Survival);
+        inventory.set_slot(
+            0,
+            Some(ItemStack {
+                block: Block::Dirt,
+                count: MAX_STACK_SIZE,
+            }),
+        );
+        inventory.set_slot(
+            3,
+            Some(ItemStack {
+                block: Block::Water(0),
+                count: 5,
+            }),
+        );
+        inventory.select(3);
+
+        let bytes = encode_inventory(&inventory, &world.registry).unwrap();
+        let decoded = decode_inventory(&bytes, &world.registry).unwrap();
+        assert_eq!(decoded, inventory);
+        assert_eq!(decoded.selected(), 3);
+    }
+
+    #[test]
+    fn missing_inventories_load_as_none() {
+        let directory = scratch_directory("missing-inventory");
+        let world = test_world(FlatTerrainGenerator { height: 0 });
+        assert_eq!(load_inventory(&directory, &world.registry).unwrap(), None);
+
+        let inventory = Inventory::with_blocks(GameMode::Creative, &[Block::Cobblestone]);
+        save_inventory(&inventory, &world.registry, &directory).unwrap();
+        assert_eq!(
+            load_inventory(&directory, &world.registry).unwrap(),
+            Some(inventory)
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn saving_replaces_another_world() {
+        let directory = scratch_directory("stale-regions");
+        let mut old = test_world(FlatTerrainGenerator { height: 0 });
+        old.insert_chunk(glm::vec2(9, 0), sample_chunk(&glm::vec2(9, 0)));
+        save_world(&old, &directory).unwrap();
+
+        let mut world = World::with_generator(
+            7,
+            old.registry.clone(),
+            Arc::new(FlatTerrainGenerator { height: 0 }),
+        );
+        world.insert_chunk(glm::vec2(0, 0), sample_chunk(&glm::vec2(0, 0)));
+        save_world(&world, &directory).unwrap();
+        assert_eq!(
+            region_paths(&directory).unwrap(),
+            vec![region_path(&directory, &glm::vec2(0, 0))]
+        );
+        let header = fs::read(directory.join(WORLD_FILE)).unwrap();
+        assert_eq!(decode_header(&header).unwrap(), 7);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn saved_chunks_stream_in_and_keep_their_edits() {
+        let directory = scratch_directory("streamed-chunks");
+        let center = glm::vec3(8.0, 40.0, 8.0);
+        let mut world = test_world(FlatTerrainGenerator { height: 4 });
+        world.view_distance = 1;
+        world.update(&center);
+        world.finish_loading();
+        world.set_block(&glm::vec3(3, 4, 5), Block::Cobblestone);
+        save_world(&world, &directory).unwrap();
+
+        // Only the edited chunk is saved
+        let region = fs::read(region_path(&directory, &glm::vec2(0, 0))).unwrap();
+        let saved = decode_region(&region, &world.registry).unwrap();
+        assert_eq!(saved.len(), 1);
+
+        let storage = Arc::new(RegionStorage {
+            directory: directory.clone(),
+            registry: world.registry.clone(),
+        });
+        let mut loaded = World::with_storage(
+            0,
+            world.registry.clone(),
+            Arc::new(FlatTerrainGenerator { height: 4 }),
+            storage,
+        );
+        assert!(loaded.chunks.is_empty());
+        loaded.view_distance = 1;
+        loaded.update(&center);
+        loaded.finish_loading();
+        assert_eq!(
+            loaded.block_at(&glm::vec3(3, 4, 5)),
+            Some(Block::Cobblestone)
+        );
+        assert!(loaded.chunks.values().all(|chunk| !chunk.modified));
+
+        // Edits to another chunk of the region are merged with the saved ones
+        loaded.set_block(&glm::vec3(20, 4, 5), Block::Dirt);
+        save_world(&loaded, &directory).unwrap();
+        let mut reloaded = load_world(&directory, world.registry.clone()).unwrap();
+        assert!(reloaded.chunks.is_empty());
+        reloaded.view_distance = 1;
+        reloaded.update(&center);
+        reloaded.finish_loading();
+        assert_eq!(
+            reloaded.block_at(&glm::vec3(3, 4, 5)),
+            Some(Block::Cobblestone)
+        );
+        assert_eq!(reloaded.block_at(&glm::vec3(20, 4, 5)), Some(Block::Dirt));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
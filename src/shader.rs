@@ -0,0 +1,206 @@
+use anyhow::Result;
+use gl::types::*;
+use std::{
+    ffi::CString,
+    fmt, fs,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the shader files are checked for changes
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Why a shader program couldn't be built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderError {
+    Read {
+        path: String,
+        message: String,
+    },
+    Compile {
+        path: String,
+        log: String,
+    },
+    Link {
+        vertex_path: String,
+        fragment_path: String,
+        log: String,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Read { path, message } => {
+                write!(f, "Failed to read shader '{}': {}", path, message)
+            }
+            ShaderError::Compile { path, log } => {
+                write!(f, "Failed to compile shader '{}':\n{}", path, log)
+            }
+            ShaderError::Link {
+                vertex_path,
+                fragment_path,
+                log,
+            } => write!(
+                f,
+                "Failed to link shaders '{}' and '{}':\n{}",
+                vertex_path, fragment_path, log
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// A linked vertex and fragment shader that is rebuilt when either file changes
+pub struct ShaderProgram {
+    id: GLuint,
+    vertex_path: String,
+    fragment_path: String,
+    /// When the files were last modified as of the last build attempt
+    modified: [Option<SystemTime>; 2],
+    last_checked: Instant,
+}
+
+impl ShaderProgram {
+    pub fn new(vertex_path: &str, fragment_path: &str) -> Result<Self, ShaderError> {
+        let modified = [modified_time(vertex_path), modified_time(fragment_path)];
+        Ok(Self {
+            id: link_program(vertex_path, fragment_path)?,
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            modified,
+            last_checked: Instant::now(),
+        })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn uniform_location(&self, name: &str) -> Result<GLint> {
+        let name = CString::new(name.as_bytes())?;
+        unsafe { Ok(gl::GetUniformLocation(self.id, name.as_ptr())) }
+    }
+
+    /// Rebuilds the program if a shader file changed since the last attempt.
+    /// Returns whether it was rebuilt. If the new sources fail to build, the
+    /// previous program stays in use until the files change again.
+    pub fn reload_if_changed(&mut self) -> Result<bool, ShaderError> {
+        if self.last_checked.elapsed() < RELOAD_CHECK_INTERVAL {
+            return Ok(false);
+        }
+        self.last_checked = Instant::now();
+        let modified = [
+            modified_time(&self.vertex_path),
+            modified_time(&self.fragment_path),
+        ];
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        let id = link_program(&self.vertex_path, &self.fragment_path)?;
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+        self.id = id;
+        Ok(true)
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.id);
+        }
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Compiles and links a vertex and fragment shader
+fn link_program(vertex_path: &str, fragment_path: &str) -> Result<GLuint, ShaderError> {
+    let vertex_shader = compile_shader(vertex_path, gl::VERTEX_SHADER)?;
+    let fragment_shader = match compile_shader(fragment_path, gl::FRAGMENT_SHADER) {
+        Ok(shader) => shader,
+        Err(error) => {
+            unsafe { gl::DeleteShader(vertex_shader) };
+            return Err(error);
+        }
+    };
+    let shaders = [vertex_shader, fragment_shader];
+    unsafe {
+        let program = gl::CreateProgram();
+        for shader in shaders.iter() {
+            gl::AttachShader(program, *shader);
+        }
+        gl::LinkProgram(program);
+        for shader in shaders.iter() {
+            gl::DeleteShader(*shader);
+        }
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+        if success == gl::TRUE as GLint {
+            return Ok(program);
+        }
+        let log = info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog);
+        gl::DeleteProgram(program);
+        Err(ShaderError::Link {
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            log,
+        })
+    }
+}
+
+fn compile_shader(path: &str, kind: GLenum) -> Result<GLuint, ShaderError> {
+    let source = fs::read_to_string(path).map_err(|error| ShaderError::Read {
+        path: path.to_string(),
+        message: error.to_string(),
+    })?;
+    let source = CString::new(source.as_bytes()).map_err(|_| ShaderError::Read {
+        path: path.to_string(),
+        message: "the source contains a nul byte".to_string(),
+    })?;
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        gl::ShaderSource(shader, 1, &source.as_ptr(), std::ptr::null());
+        gl::CompileShader(shader);
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        if success == gl::TRUE as GLint {
+            return Ok(shader);
+        }
+        let log = info_log(shader, gl::GetShaderiv, gl::GetShaderInfoLog);
+        gl::DeleteShader(shader);
+        Err(ShaderError::Compile {
+            path: path.to_string(),
+            log,
+        })
+    }
+}
+
+/// Reads the info log of a shader or program through the matching pair of GL functions
+unsafe fn info_log(
+    id: GLuint,
+    get_parameter: unsafe fn(GLuint, GLenum, *mut GLint),
+    get_log: unsafe fn(GLuint, GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut info_log_length = 0;
+    get_parameter(id, gl::INFO_LOG_LENGTH, &mut info_log_length);
+    let mut info_log = vec![0u8; info_log_length.max(0) as usize];
+    let mut written = 0;
+    get_log(
+        id,
+        info_log_length,
+        &mut written,
+        info_log.as_mut_ptr() as *mut GLchar,
+    );
+    info_log.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&info_log).trim_end().to_string()
+}
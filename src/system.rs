@@ -1,24 +1,118 @@
+use anyhow::Result;
 use glutin::{
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{Event, WindowEvent},
+    window::Window,
 };
 use nalgebra_glm as glm;
-use std::{cmp, time::Instant};
+use std::{cell::Cell, cmp, rc::Rc, time::Instant};
+
+/// The window operations the app uses, so it can also run without a window
+pub trait WindowControl {
+    /// Hides the cursor, keeps it in the window and moves it to a position
+    fn grab_cursor(&self, position: glm::Vec2) -> Result<()>;
+    fn set_title(&self, title: &str);
+}
+
+impl WindowControl for Window {
+    fn grab_cursor(&self, position: glm::Vec2) -> Result<()> {
+        self.set_cursor_grab(true)?;
+        self.set_cursor_visible(false);
+        self.set_cursor_position(PhysicalPosition::new(position.x, position.y))?;
+        Ok(())
+    }
+
+    fn set_title(&self, title: &str) {
+        Window::set_title(self, title);
+    }
+}
+
+/// Stands in for the window when replaying a recording
+pub struct NoWindow;
+
+impl WindowControl for NoWindow {
+    fn grab_cursor(&self, _position: glm::Vec2) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_title(&self, _title: &str) {}
+}
+
+/// Measures the time between frames
+pub trait Clock {
+    /// Seconds since the previous call, or since the clock was created
+    fn elapsed(&mut self) -> f64;
+}
+
+/// Measures wall clock time
+pub struct SystemClock {
+    last_frame: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            last_frame: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&mut self) -> f64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame).as_micros() as f64 / 1_000_000_f64;
+        self.last_frame = now;
+        elapsed
+    }
+}
+
+/// A clock whose time only passes when it is advanced, for replays and tests.
+/// Clones share their time, so a handle can be kept after giving one to a `System`.
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    pending: Rc<Cell<f64>>,
+}
+
+impl ManualClock {
+    pub fn advance(&self, seconds: f64) {
+        self.pending.set(self.pending.get() + seconds);
+    }
+}
+
+impl Clock for ManualClock {
+    fn elapsed(&mut self) -> f64 {
+        self.pending.replace(0.0)
+    }
+}
+
+/// Seconds of frames averaged into each frame rate measurement
+const FRAME_RATE_INTERVAL: f64 = 0.5;
 
 pub struct System {
     pub window_dimensions: [u32; 2], // TODO: Change this to a glm::Vec2
     pub delta_time: f64,
-    pub last_frame: Instant,
+    /// Frames per second, averaged over the last `FRAME_RATE_INTERVAL`
+    pub frame_rate: f64,
     pub exit_requested: bool,
+    clock: Box<dyn Clock>,
+    frames_counted: u32,
+    time_counted: f64,
 }
 
 impl System {
     pub fn new(window_dimensions: [u32; 2]) -> Self {
+        Self::with_clock(window_dimensions, Box::new(SystemClock::default()))
+    }
+
+    pub fn with_clock(window_dimensions: [u32; 2], clock: Box<dyn Clock>) -> Self {
         Self {
-            last_frame: Instant::now(),
+            clock,
             window_dimensions,
             delta_time: 0.01,
+            frame_rate: 0.0,
             exit_requested: false,
+            frames_counted: 0,
+            time_counted: 0.0,
         }
     }
 
@@ -35,13 +129,21 @@ impl System {
         )
     }
 
+    fn count_frame(&mut self) {
+        self.frames_counted += 1;
+        self.time_counted += self.delta_time;
+        if self.time_counted >= FRAME_RATE_INTERVAL {
+            self.frame_rate = self.frames_counted as f64 / self.time_counted;
+            self.frames_counted = 0;
+            self.time_counted = 0.0;
+        }
+    }
+
     pub fn handle_event<T>(&mut self, event: &Event<T>) {
         match event {
             Event::NewEvents { .. } => {
-                self.delta_time = (Instant::now().duration_since(self.last_frame).as_micros()
-                    as f64)
-                    / 1_000_000_f64;
-                self.last_frame = Instant::now();
+                self.delta_time = self.clock.elapsed();
+                self.count_frame();
             }
             Event::WindowEvent { event, .. } => match *event {
                 WindowEvent::CloseRequested => self.exit_requested = true,
@@ -54,3 +156,118 @@ impl System {
         }
     }
 }
+
+/// Accumulates frame time and reports how many fixed-size steps to simulate,
+/// so simulation results don't depend on the frame rate
+pub struct FixedTimestep {
+    pub step: f64,
+    /// The most steps a single frame can run. Time beyond that is dropped,
+    /// so a slow frame can't cause even more work in the next one.
+    pub max_steps: u32,
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    /// Creates a timestep running `tick_rate` steps per second
+    pub fn new(tick_rate: f64, max_steps: u32) -> Self {
+        Self {
+            step: 1.0 / tick_rate,
+            max_steps,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, delta_time: f64) -> u32 {
+        self.accumulator += delta_time;
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            if steps == self.max_steps {
+                self.accumulator %= self.step;
+                break;
+            }
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How far the time is between the last step and the next one, from 0 to 1
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / self.step
+    }
+}
+
+/// A position at the last two simulation steps, so frames drawn between
+/// steps can show it partway along its movement
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterpolatedPosition {
+    pub previous: glm::Vec3,
+    pub current: glm::Vec3,
+}
+
+impl InterpolatedPosition {
+    pub fn new(position: glm::Vec3) -> Self {
+        Self {
+            previous: position,
+            current: position,
+        }
+    }
+
+    /// Records the position after a step
+    pub fn push(&mut self, position: glm::Vec3) {
+        self.previous = self.current;
+        self.current = position;
+    }
+
+    pub fn at(&self, alpha: f32) -> glm::Vec3 {
+        glm::lerp(&self.previous, &self.current, alpha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_carry_leftover_time_into_alpha() {
+        let mut timestep = FixedTimestep::new(4.0, 5);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.3125), 1);
+        assert_eq!(timestep.alpha(), 0.75);
+        assert_eq!(timestep.advance(0.3125), 2);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn long_frames_are_capped_at_max_steps() {
+        let mut timestep = FixedTimestep::new(4.0, 3);
+        assert_eq!(timestep.advance(2.125), 3);
+        // The dropped time doesn't run in later frames, only the part of a step does
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn manual_clock_reports_time_once() {
+        let clock = ManualClock::default();
+        let mut system = System::with_clock([640, 480], Box::new(clock.clone()));
+        clock.advance(0.25);
+        clock.advance(0.5);
+        system.handle_event(&Event::<()>::NewEvents(glutin::event::StartCause::Poll));
+        assert_eq!(system.delta_time, 0.75);
+        system.handle_event(&Event::<()>::NewEvents(glutin::event::StartCause::Poll));
+        assert_eq!(system.delta_time, 0.0);
+    }
+
+    #[test]
+    fn interpolates_between_the_last_two_steps() {
+        let mut position = InterpolatedPosition::new(glm::vec3(0.0, 0.0, 0.0));
+        position.push(glm::vec3(2.0, 0.0, 0.0));
+        position.push(glm::vec3(4.0, 2.0, 0.0));
+        assert_eq!(position.at(0.0), glm::vec3(2.0, 0.0, 0.0));
+        assert_eq!(position.at(0.5), glm::vec3(3.0, 1.0, 0.0));
+        assert_eq!(position.at(1.0), glm::vec3(4.0, 2.0, 0.0));
+    }
+}
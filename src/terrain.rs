@@ -0,0 +1,289 @@
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use crate::{
+    block::Block,
+    world::{Chunk, ChunkPosition, CHUNK_DEPTH, CHUNK_LENGTH, CHUNK_WIDTH},
+};
+
+const BASE_HEIGHT: f64 = 24.0;
+const HEIGHT_VARIATION: f64 = 20.0;
+const TERRAIN_SCALE: f64 = 1.0 / 96.0;
+const OCTAVES: usize = 4;
+const DIRT_DEPTH: i32 = 4;
+const FLOWER_CHANCE: f64 = 0.02;
+
+/// Fills chunks with blocks. Implementations must be deterministic
+/// so the same chunk is produced every time it is streamed in.
+pub trait TerrainGenerator: Send + Sync {
+    fn generate(&self, position: &ChunkPosition) -> Chunk;
+}
+
+/// Generates rolling hills from a seeded simplex noise heightmap
+pub struct NoiseTerrainGenerator {
+    seed: u64,
+    heightmap: SimplexNoise,
+    gravel: SimplexNoise,
+}
+
+impl NoiseTerrainGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            heightmap: SimplexNoise::new(seed),
+            gravel: SimplexNoise::new(seed.wrapping_add(1)),
+        }
+    }
+
+    /// The height of the topmost solid block in a column
+    pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let noise =
+            self.heightmap
+                .fractal(x as f64 * TERRAIN_SCALE, z as f64 * TERRAIN_SCALE, OCTAVES);
+        let height = BASE_HEIGHT + noise * HEIGHT_VARIATION;
+        (height as i32).clamp(1, CHUNK_DEPTH as i32 - 2)
+    }
+
+    fn column(&self, x: i32, z: i32) -> [Block; CHUNK_DEPTH] {
+        let mut column = [Block::Air; CHUNK_DEPTH];
+        let surface = self.surface_height(x, z);
+        let gravel_depth = if self.gravel.sample(x as f64 / 24.0, z as f64 / 24.0) > 0.3 {
+            1
+        } else {
+            DIRT_DEPTH
+        };
+
+        for (y, block) in column.iter_mut().enumerate() {
+            let y = y as i32;
+            *block = if y == 0 {
+                Block::Bedrock
+            } else if y < surface - gravel_depth {
+                Block::Gravel
+            } else if y < surface {
+                Block::Dirt
+            } else if y == surface {
+                Block::DirtWithGrass
+            } else {
+                Block::Air
+            };
+        }
+
+        let roll = hash_to_unit(self.seed, x, z);
+        if roll < FLOWER_CHANCE {
+            column[surface as usize + 1] = if roll < FLOWER_CHANCE / 2.0 {
+                Block::Rose
+            } else {
+                Block::Thistle
+            };
+        }
+
+        column
+    }
+}
+
+impl TerrainGenerator for NoiseTerrainGenerator {
+    fn generate(&self, position: &ChunkPosition) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        let origin = chunk.origin();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_LENGTH {
+                chunk.blocks[x][z] = self.column(origin.x + x as i32, origin.z + z as i32);
+            }
+        }
+        chunk
+    }
+}
+
+/// Fills chunks with dirt up to a height, for tests that need plain ground
+#[cfg(test)]
+pub struct FlatTerrainGenerator {
+    /// How many layers of dirt each column has
+    pub height: usize,
+}
+
+#[cfg(test)]
+impl TerrainGenerator for FlatTerrainGenerator {
+    fn generate(&self, position: &ChunkPosition) -> Chunk {
+        let mut chunk = Chunk::new(position);
+        for column in chunk.blocks.iter_mut().flatten() {
+            for (y, block) in column.iter_mut().enumerate() {
+                *block = if y < self.height {
+                    Block::Dirt
+                } else {
+                    Block::Air
+                };
+            }
+        }
+        chunk
+    }
+}
+
+/// Maps a seed and column to a pseudo-random value in [0, 1)
+fn hash_to_unit(seed: u64, x: i32, z: i32) -> f64 {
+    let mut hash = seed ^ ((x as u32 as u64) << 32 | z as u32 as u64);
+    hash = splitmix64(&mut hash);
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    (FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    (-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+/// 2D simplex noise with a permutation table shuffled from a seed
+pub struct SimplexNoise {
+    permutation: [u8; 512],
+}
+
+impl SimplexNoise {
+    pub fn new(seed: u64) -> Self {
+        let mut table = [0u8; 256];
+        for (index, value) in table.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+
+        let mut state = seed;
+        for index in (1..table.len()).rev() {
+            let other = (splitmix64(&mut state) % (index as u64 + 1)) as usize;
+            table.swap(index, other);
+        }
+
+        let mut permutation = [0u8; 512];
+        for (index, value) in permutation.iter_mut().enumerate() {
+            *value = table[index & 255];
+        }
+        Self { permutation }
+    }
+
+    /// Sums octaves of noise, each at twice the frequency and half the amplitude of the last.
+    /// The result is normalized to roughly [-1, 1].
+    pub fn fractal(&self, x: f64, y: f64, octaves: usize) -> f64 {
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut max_amplitude = 0.0;
+        for _ in 0..octaves {
+            total += self.sample(x * frequency, y * frequency) * amplitude;
+            max_amplitude += amplitude;
+            frequency *= 2.0;
+            amplitude *= 0.5;
+        }
+        total / max_amplitude
+    }
+
+    /// Samples the noise at a point, returning a value in roughly [-1, 1]
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        let skew = 0.5 * (3.0_f64.sqrt() - 1.0);
+        let unskew = (3.0 - 3.0_f64.sqrt()) / 6.0;
+
+        let s = (x + y) * skew;
+        let i = (x + s).floor();
+        let j = (y + s).floor();
+        let t = (i + j) * unskew;
+        let x0 = x - (i - t);
+        let y0 = y - (j - t);
+
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let x1 = x0 - i1 as f64 + unskew;
+        let y1 = y0 - j1 as f64 + unskew;
+        let x2 = x0 - 1.0 + 2.0 * unskew;
+        let y2 = y0 - 1.0 + 2.0 * unskew;
+
+        let ii = (i as i64 & 255) as usize;
+        let jj = (j as i64 & 255) as usize;
+
+        let corners = [
+            (x0, y0, self.gradient_index(ii, jj)),
+            (x1, y1, self.gradient_index(ii + i1, jj + j1)),
+            (x2, y2, self.gradient_index(ii + 1, jj + 1)),
+        ];
+
+        let total: f64 = corners
+            .iter()
+            .map(|&(x, y, gradient)| {
+                let falloff = 0.5 - x * x - y * y;
+                if falloff < 0.0 {
+                    return 0.0;
+                }
+                let (gx, gy) = GRADIENTS[gradient];
+                falloff.powi(4) * (gx * x + gy * y)
+            })
+            .sum();
+
+        // Scale the result to roughly fill [-1, 1]
+        70.0 * total
+    }
+
+    fn gradient_index(&self, i: usize, j: usize) -> usize {
+        self.permutation[i + self.permutation[j] as usize] as usize % GRADIENTS.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_generates_the_same_chunk() {
+        let position = glm::vec2(3, -7);
+        let first = NoiseTerrainGenerator::new(42).generate(&position);
+        let second = NoiseTerrainGenerator::new(42).generate(&position);
+        assert!(first.blocks == second.blocks);
+        assert_eq!(first.position, second.position);
+    }
+
+    #[test]
+    fn different_seeds_generate_different_chunks() {
+        let position = glm::vec2(3, -7);
+        let first = NoiseTerrainGenerator::new(42).generate(&position);
+        let second = NoiseTerrainGenerator::new(43).generate(&position);
+        assert!(first.blocks != second.blocks);
+    }
+
+    #[test]
+    fn columns_are_layered_from_bedrock_to_grass() {
+        let generator = NoiseTerrainGenerator::new(7);
+        let position = glm::vec2(-2, 5);
+        let chunk = generator.generate(&position);
+        let origin = chunk.origin();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_LENGTH {
+                let column = &chunk.blocks[x][z];
+                let surface = generator.surface_height(origin.x + x as i32, origin.z + z as i32);
+                assert_eq!(column[0], Block::Bedrock);
+                assert_eq!(column[surface as usize], Block::DirtWithGrass);
+                for block in column[1..surface as usize].iter() {
+                    assert!(matches!(block, Block::Dirt | Block::Gravel));
+                }
+                for block in column[surface as usize + 2..].iter() {
+                    assert_eq!(*block, Block::Air);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn noise_stays_roughly_within_range() {
+        let noise = SimplexNoise::new(1);
+        for step in 0..1000 {
+            let value = noise.fractal(step as f64 * 0.37, step as f64 * -0.11, OCTAVES);
+            assert!((-1.0..=1.0).contains(&value));
+        }
+    }
+}
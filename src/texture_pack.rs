@@ -0,0 +1,666 @@
+use anyhow::{bail, Context, Result};
+use image::{GenericImageView, RgbaImage};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::PathBuf,
+};
+
+/// The pack the game ships with, which every other pack's tiles are arranged to match
+pub const DEFAULT_PACK: &str = "assets/textures";
+/// Where extra packs are found, as directories or zip files
+pub const PACK_DIRECTORY: &str = "texture_packs";
+const MANIFEST_FILE: &str = "pack.ron";
+/// How many animated tiles can be drawn at once, matching block.vs.glsl
+pub const MAX_ANIMATED_TILES: usize = 16;
+
+/// Where a pack's tile images come from
+#[derive(Debug, Clone, Deserialize)]
+pub enum PackTiles {
+    /// A single image holding a grid of tiles, with tiles named by their (column, row)
+    Atlas {
+        image: String,
+        names: HashMap<String, (u32, u32)>,
+    },
+    /// One image per tile, read from tiles/<name>.png and packed in the listed order
+    Files(Vec<String>),
+}
+
+/// The pack.ron file at the root of a texture pack
+#[derive(Debug, Clone, Deserialize)]
+pub struct PackManifest {
+    pub name: String,
+    /// The width and height of every tile in pixels, which must be a power of two.
+    /// Packs of separate tile images can leave it out to use the size of their first tile.
+    #[serde(default, deserialize_with = "present_tile_size")]
+    pub tile_size: Option<u32>,
+    pub tiles: PackTiles,
+    /// Named tiles that cycle through other tiles, each given as a tile name
+    /// and the seconds it is shown for
+    #[serde(default)]
+    pub animations: HashMap<String, Vec<(String, f64)>>,
+}
+
+/// Reads a tile size given as a plain number, so it doesn't have to be written as Some(16)
+fn present_tile_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    u32::deserialize(deserializer).map(Some)
+}
+
+/// Reads files by their path inside a pack
+trait PackFiles {
+    fn read(&mut self, path: &str) -> Result<Vec<u8>>;
+}
+
+struct DirectoryFiles(PathBuf);
+
+impl PackFiles for DirectoryFiles {
+    fn read(&mut self, path: &str) -> Result<Vec<u8>> {
+        let path = self.0.join(path);
+        fs::read(&path).with_context(|| format!("Failed to read '{}'", path.display()))
+    }
+}
+
+struct ZipFiles(zip::ZipArchive<fs::File>);
+
+impl PackFiles for ZipFiles {
+    fn read(&mut self, path: &str) -> Result<Vec<u8>> {
+        let mut file = self
+            .0
+            .by_name(path)
+            .with_context(|| format!("Failed to find '{}' in the zip file", path))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .with_context(|| format!("Failed to extract '{}'", path))?;
+        Ok(bytes)
+    }
+}
+
+/// A tile that cycles through the layers of other tiles
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileAnimation {
+    /// The layer meshes refer to, which is that of the first frame
+    pub tile: usize,
+    /// Layers with the seconds each one is shown for
+    frames: Vec<(usize, f64)>,
+    duration: f64,
+}
+
+impl TileAnimation {
+    pub fn new(frames: Vec<(usize, f64)>) -> Result<Self> {
+        let tile = match frames.first() {
+            Some((layer, _)) => *layer,
+            None => bail!("An animation needs at least one frame"),
+        };
+        if let Some((layer, seconds)) = frames
+            .iter()
+            .find(|(_, seconds)| *seconds <= 0.0 || !seconds.is_finite())
+        {
+            bail!(
+                "The frame showing layer {} lasts {} seconds, but frames must last a positive, finite time",
+                layer,
+                seconds
+            );
+        }
+        Ok(Self {
+            tile,
+            duration: frames.iter().map(|(_, seconds)| seconds).sum(),
+            frames,
+        })
+    }
+
+    /// The layer shown at a time in seconds, looping through the frames forever
+    pub fn frame_at(&self, time: f64) -> usize {
+        let mut remaining = time.rem_euclid(self.duration);
+        for (layer, seconds) in self.frames.iter() {
+            if remaining < *seconds {
+                return *layer;
+            }
+            remaining -= seconds;
+        }
+        // Rounding can leave a sliver of time past the last frame
+        self.frames[self.frames.len() - 1].0
+    }
+}
+
+/// The animated tiles paired with the layers they show at a time in seconds
+pub fn current_frames(animations: &[TileAnimation], time: f64) -> Vec<(i32, i32)> {
+    animations
+        .iter()
+        .map(|animation| (animation.tile as i32, animation.frame_at(time) as i32))
+        .collect()
+}
+
+/// The block tiles of a texture pack, one image per texture array layer
+#[derive(Debug, Clone)]
+pub struct TexturePack {
+    pub name: String,
+    pub tile_size: u32,
+    tiles: Vec<RgbaImage>,
+    names: HashMap<String, usize>,
+    animations: Vec<TileAnimation>,
+}
+
+impl TexturePack {
+    /// Loads a pack from a directory or a zip file with a pack.ron manifest at its root
+    pub fn load(path: &str) -> Result<Self> {
+        let result = if path.ends_with(".zip") {
+            fs::File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| Ok(zip::ZipArchive::new(file)?))
+                .and_then(|archive| Self::read(&mut ZipFiles(archive)))
+        } else {
+            Self::read(&mut DirectoryFiles(PathBuf::from(path)))
+        };
+        result.with_context(|| format!("Failed to load texture pack '{}'", path))
+    }
+
+    fn read(files: &mut dyn PackFiles) -> Result<Self> {
+        let manifest: PackManifest = ron::de::from_bytes(&files.read(MANIFEST_FILE)?)
+            .with_context(|| format!("Failed to parse '{}'", MANIFEST_FILE))?;
+        let mut pack = match &manifest.tiles {
+            PackTiles::Atlas { image, names } => {
+                let tile_size = manifest
+                    .tile_size
+                    .context("Packs with an atlas must give their tile_size")?;
+                let atlas = decode_image(&files.read(image)?, image)?;
+                Self::from_atlas(&manifest.name, tile_size, &atlas, names)
+            }
+            PackTiles::Files(names) => {
+                let tiles = names
+                    .iter()
+                    .map(|name| {
+                        let path = format!("tiles/{}.png", name);
+                        decode_image(&files.read(&path)?, &path)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let tile_size = match (manifest.tile_size, tiles.first()) {
+                    (Some(tile_size), _) => tile_size,
+                    (None, Some(tile)) => tile.width(),
+                    (None, None) => bail!("The pack has no tiles to take the tile size from"),
+                };
+                Self::from_tiles(&manifest.name, tile_size, names, tiles)
+            }
+        }?;
+        pack.add_animations(&manifest.animations)?;
+        Ok(pack)
+    }
+
+    /// Splits an atlas into tiles, counted left to right and top to bottom
+    pub fn from_atlas(
+        name: &str,
+        tile_size: u32,
+        atlas: &RgbaImage,
+        names: &HashMap<String, (u32, u32)>,
+    ) -> Result<Self> {
+        check_tile_size(tile_size)?;
+        let (width, height) = atlas.dimensions();
+        if width == 0 || height == 0 || width % tile_size != 0 || height % tile_size != 0 {
+            bail!(
+                "The atlas is {}x{} pixels, which isn't a whole number of {} pixel tiles",
+                width,
+                height,
+                tile_size
+            );
+        }
+        let (columns, rows) = (width / tile_size, height / tile_size);
+        let mut indices = HashMap::new();
+        for (tile_name, &(column, row)) in names.iter() {
+            if column >= columns || row >= rows {
+                bail!(
+                    "Tile '{}' at column {} and row {} is outside the atlas, which is {} tiles wide and {} tall",
+                    tile_name,
+                    column,
+                    row,
+                    columns,
+                    rows
+                );
+            }
+            indices.insert(tile_name.clone(), (row * columns + column) as usize);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            tile_size,
+            tiles: split_tiles(atlas, tile_size),
+            names: indices,
+            animations: Vec::new(),
+        })
+    }
+
+    /// Packs separate tile images in order, naming each one
+    pub fn from_tiles(
+        name: &str,
+        tile_size: u32,
+        names: &[String],
+        tiles: Vec<RgbaImage>,
+    ) -> Result<Self> {
+        check_tile_size(tile_size)?;
+        if names.len() != tiles.len() {
+            bail!(
+                "There are {} tile names for {} tiles",
+                names.len(),
+                tiles.len()
+            );
+        }
+        let mut seen = HashSet::new();
+        for (tile_name, tile) in names.iter().zip(tiles.iter()) {
+            if !seen.insert(tile_name) {
+                bail!("Tile '{}' is listed more than once", tile_name);
+            }
+            if tile.dimensions() != (tile_size, tile_size) {
+                bail!(
+                    "Tile '{}' is {}x{} pixels, but the pack's tiles are {}x{}",
+                    tile_name,
+                    tile.width(),
+                    tile.height(),
+                    tile_size,
+                    tile_size
+                );
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            tile_size,
+            tiles,
+            names: names
+                .iter()
+                .enumerate()
+                .map(|(index, tile_name)| (tile_name.clone(), index))
+                .collect(),
+            animations: Vec::new(),
+        })
+    }
+
+    /// Names animated tiles made of this pack's tiles. Each one refers
+    /// to the layer of its first frame, which shows its current frame instead.
+    pub fn add_animations(
+        &mut self,
+        animations: &HashMap<String, Vec<(String, f64)>>,
+    ) -> Result<()> {
+        if self.animations.len() + animations.len() > MAX_ANIMATED_TILES {
+            bail!(
+                "There are {} animated tiles, but at most {} can be drawn",
+                self.animations.len() + animations.len(),
+                MAX_ANIMATED_TILES
+            );
+        }
+        let mut names = animations.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            if self.names.contains_key(name) {
+                bail!("Animated tile '{}' has the same name as a tile", name);
+            }
+            let frames = animations[name]
+                .iter()
+                .map(|(tile, seconds)| match self.tile_index(tile) {
+                    Some(layer) => Ok((layer, *seconds)),
+                    None => bail!(
+                        "Animated tile '{}' shows tile '{}', which isn't named",
+                        name,
+                        tile
+                    ),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let animation = TileAnimation::new(frames)
+                .with_context(|| format!("Failed to animate tile '{}'", name))?;
+            if self
+                .animations
+                .iter()
+                .any(|other| other.tile == animation.tile)
+            {
+                bail!(
+                    "Animated tile '{}' starts on the same tile as another animation",
+                    name
+                );
+            }
+            self.names.insert(name.clone(), animation.tile);
+            self.animations.push(animation);
+        }
+        Ok(())
+    }
+
+    pub fn animations(&self) -> &[TileAnimation] {
+        &self.animations
+    }
+
+    /// The texture array layer of a named tile
+    pub fn tile_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Lays this pack's tiles out in the same layers as another pack's,
+    /// so a tile index means the same thing with either pack.
+    /// Every tile named in the layout must be named here too. Unnamed layers
+    /// take this pack's tile at the same index, or are left transparent.
+    pub fn layers_like(&self, layout: &TexturePack) -> Result<Vec<RgbaImage>> {
+        let mut missing = layout
+            .names
+            .keys()
+            .filter(|name| !self.names.contains_key(*name))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            bail!(
+                "Texture pack '{}' is missing the tiles {}",
+                self.name,
+                missing.join(", ")
+            );
+        }
+        let mut layers = (0..layout.tiles.len())
+            .map(|index| {
+                self.tiles
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(|| RgbaImage::new(self.tile_size, self.tile_size))
+            })
+            .collect::<Vec<_>>();
+        for (name, layer) in layout.names.iter() {
+            layers[*layer] = self.tiles[self.names[name]].clone();
+        }
+        Ok(layers)
+    }
+}
+
+/// Loads a pack's tiles arranged like the default pack, ready for a texture array
+pub fn load_pack_layers(path: &str) -> Result<Vec<RgbaImage>> {
+    let default = TexturePack::load(DEFAULT_PACK)?;
+    if path == DEFAULT_PACK {
+        return Ok(default.tiles);
+    }
+    TexturePack::load(path)?.layers_like(&default)
+}
+
+/// The default pack followed by the packs in `PACK_DIRECTORY`, sorted by path
+pub fn available_packs() -> Vec<String> {
+    let mut packs = fs::read_dir(PACK_DIRECTORY)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir() || path.extension() == Some("zip".as_ref()))
+                .filter_map(|path| path.to_str().map(str::to_string))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    packs.sort();
+    packs.insert(0, DEFAULT_PACK.to_string());
+    packs
+}
+
+/// Cuts an image into square tiles, counted left to right and top to bottom
+pub fn split_tiles(image: &RgbaImage, tile_size: u32) -> Vec<RgbaImage> {
+    let (columns, rows) = (image.width() / tile_size, image.height() / tile_size);
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            image
+                .view(column * tile_size, row * tile_size, tile_size, tile_size)
+                .to_image()
+        })
+        .collect()
+}
+
+fn check_tile_size(tile_size: u32) -> Result<()> {
+    if !tile_size.is_power_of_two() {
+        bail!("The tile size {} isn't a power of two", tile_size);
+    }
+    Ok(())
+}
+
+fn decode_image(bytes: &[u8], path: &str) -> Result<RgbaImage> {
+    Ok(image::load_from_memory(bytes)
+        .with_context(|| format!("Failed to decode image '{}'", path))?
+        .to_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack files held in memory
+    struct MemoryFiles(HashMap<String, Vec<u8>>);
+
+    impl PackFiles for MemoryFiles {
+        fn read(&mut self, path: &str) -> Result<Vec<u8>> {
+            self.0
+                .get(path)
+                .cloned()
+                .with_context(|| format!("Failed to find '{}'", path))
+        }
+    }
+
+    fn png(size: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(RgbaImage::new(size, size))
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn error_message<T>(result: Result<T>) -> String {
+        match result {
+            Ok(_) => panic!("Expected an error"),
+            Err(error) => format!("{:#}", error),
+        }
+    }
+
+    fn animation() -> TileAnimation {
+        TileAnimation::new(vec![(4, 0.25), (5, 0.5), (6, 0.25)]).unwrap()
+    }
+
+    #[test]
+    fn frames_change_at_their_boundaries() {
+        let animation = animation();
+        assert_eq!(animation.tile, 4);
+        assert_eq!(animation.frame_at(0.0), 4);
+        assert_eq!(animation.frame_at(0.2499), 4);
+        assert_eq!(animation.frame_at(0.25), 5);
+        assert_eq!(animation.frame_at(0.7499), 5);
+        assert_eq!(animation.frame_at(0.75), 6);
+        assert_eq!(animation.frame_at(0.9999), 6);
+    }
+
+    #[test]
+    fn frames_loop_past_the_duration() {
+        let animation = animation();
+        assert_eq!(animation.frame_at(1.0), 4);
+        assert_eq!(animation.frame_at(1.5), 5);
+        assert_eq!(animation.frame_at(1000.8), 6);
+    }
+
+    #[test]
+    fn negative_times_count_back_from_the_end() {
+        let animation = animation();
+        assert_eq!(animation.frame_at(-0.1), 6);
+        assert_eq!(animation.frame_at(-0.5), 5);
+        assert_eq!(animation.frame_at(-1.0), 4);
+    }
+
+    #[test]
+    fn frames_must_last_a_positive_time() {
+        assert!(TileAnimation::new(Vec::new()).is_err());
+        for seconds in [0.0, -0.5, f64::NAN, f64::INFINITY].iter() {
+            let error = error_message(TileAnimation::new(vec![(0, 0.5), (1, *seconds)]));
+            assert!(error.contains("positive, finite time"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn current_frames_pair_tiles_with_layers() {
+        let animations = [animation(), TileAnimation::new(vec![(9, 1.0)]).unwrap()];
+        assert_eq!(current_frames(&animations, 0.5), vec![(4, 5), (9, 9)]);
+    }
+
+    #[test]
+    fn tiles_of_another_size_are_rejected() {
+        let tiles = vec![RgbaImage::new(16, 16), RgbaImage::new(8, 8)];
+        let error = error_message(TexturePack::from_tiles(
+            "test",
+            16,
+            &names(&["dirt", "grass"]),
+            tiles,
+        ));
+        assert!(error.contains("Tile 'grass' is 8x8 pixels"), "{}", error);
+    }
+
+    #[test]
+    fn tile_sizes_must_be_powers_of_two() {
+        let atlas = RgbaImage::new(24, 24);
+        let error = error_message(TexturePack::from_atlas("test", 12, &atlas, &HashMap::new()));
+        assert!(error.contains("isn't a power of two"), "{}", error);
+        let error = error_message(TexturePack::from_tiles(
+            "test",
+            0,
+            &names(&["dirt"]),
+            vec![RgbaImage::new(0, 0)],
+        ));
+        assert!(error.contains("isn't a power of two"), "{}", error);
+    }
+
+    #[test]
+    fn atlas_coordinates_must_be_inside_the_atlas() {
+        let atlas = RgbaImage::new(64, 32);
+        let mut names = HashMap::new();
+        names.insert("dirt".to_string(), (3, 1));
+        let pack = TexturePack::from_atlas("test", 16, &atlas, &names).unwrap();
+        assert_eq!(pack.tile_index("dirt"), Some(7));
+
+        names.insert("grass".to_string(), (1, 2));
+        let error = error_message(TexturePack::from_atlas("test", 16, &atlas, &names));
+        assert!(
+            error.contains("Tile 'grass' at column 1 and row 2 is outside the atlas"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn packs_must_name_every_tile_of_the_layout() {
+        let layout = TexturePack::from_tiles(
+            "layout",
+            16,
+            &names(&["dirt", "grass", "gravel"]),
+            vec![RgbaImage::new(16, 16); 3],
+        )
+        .unwrap();
+        let pack = TexturePack::from_tiles(
+            "partial",
+            32,
+            &names(&["grass"]),
+            vec![RgbaImage::new(32, 32)],
+        )
+        .unwrap();
+        let error = error_message(pack.layers_like(&layout));
+        assert!(
+            error.contains("'partial' is missing the tiles dirt, gravel"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn tile_files_give_the_tile_size() {
+        let mut files = HashMap::new();
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(name: "Files", tiles: Files(["dirt", "grass"]))"#.to_vec(),
+        );
+        files.insert("tiles/dirt.png".to_string(), png(32));
+        files.insert("tiles/grass.png".to_string(), png(32));
+        let pack = TexturePack::read(&mut MemoryFiles(files.clone())).unwrap();
+        assert_eq!(pack.tile_size, 32);
+        assert_eq!(pack.tile_index("grass"), Some(1));
+
+        files.insert("tiles/grass.png".to_string(), png(16));
+        let error = error_message(TexturePack::read(&mut MemoryFiles(files)));
+        assert!(error.contains("Tile 'grass' is 16x16 pixels"), "{}", error);
+    }
+
+    #[test]
+    fn atlases_need_a_tile_size() {
+        let mut files = HashMap::new();
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(name: "Atlas", tiles: Atlas(image: "atlas.png", names: {"dirt": (0, 0)}))"#
+                .to_vec(),
+        );
+        files.insert("atlas.png".to_string(), png(32));
+        let error = error_message(TexturePack::read(&mut MemoryFiles(files.clone())));
+        assert!(error.contains("must give their tile_size"), "{}", error);
+
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(name: "Atlas", tile_size: 16, tiles: Atlas(image: "atlas.png", names: {"dirt": (1, 1)}))"#
+                .to_vec(),
+        );
+        let pack = TexturePack::read(&mut MemoryFiles(files)).unwrap();
+        assert_eq!(pack.tile_size, 16);
+        assert_eq!(pack.tile_index("dirt"), Some(3));
+    }
+
+    #[test]
+    fn animated_tiles_show_their_frames() {
+        let mut files = HashMap::new();
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(
+                name: "Animated",
+                tiles: Files(["dirt", "water_0", "water_1", "water_2"]),
+                animations: {"water": [("water_0", 0.5), ("water_1", 0.25), ("water_2", 0.25)]},
+            )"#
+            .to_vec(),
+        );
+        for name in ["dirt", "water_0", "water_1", "water_2"].iter() {
+            files.insert(format!("tiles/{}.png", name), png(16));
+        }
+        let pack = TexturePack::read(&mut MemoryFiles(files.clone())).unwrap();
+        assert_eq!(pack.tile_index("water"), Some(1));
+        assert_eq!(current_frames(pack.animations(), 0.6), vec![(1, 2)]);
+        assert_eq!(current_frames(pack.animations(), 0.8), vec![(1, 3)]);
+
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(
+                name: "Animated",
+                tiles: Files(["dirt", "water_0", "water_1", "water_2"]),
+                animations: {"water": [("water_0", 0.5), ("water_3", 0.5)]},
+            )"#
+            .to_vec(),
+        );
+        let error = error_message(TexturePack::read(&mut MemoryFiles(files.clone())));
+        assert!(
+            error.contains("shows tile 'water_3', which isn't named"),
+            "{}",
+            error
+        );
+
+        files.insert(
+            MANIFEST_FILE.to_string(),
+            br#"(
+                name: "Animated",
+                tiles: Files(["dirt", "water_0", "water_1", "water_2"]),
+                animations: {"dirt": [("water_0", 0.5)]},
+            )"#
+            .to_vec(),
+        );
+        let error = error_message(TexturePack::read(&mut MemoryFiles(files)));
+        assert!(error.contains("has the same name as a tile"), "{}", error);
+    }
+
+    #[test]
+    fn default_water_is_translucent() {
+        let pack = TexturePack::load(DEFAULT_PACK).unwrap();
+        let alpha = |name: &str| {
+            let tile = &pack.tiles[pack.tile_index(name).unwrap()];
+            tile.pixels().map(|pixel| pixel[3]).max().unwrap()
+        };
+        assert!(alpha("water") < 255);
+        assert_eq!(alpha("lava"), 255);
+    }
+}
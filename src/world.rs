@@ -0,0 +1,728 @@
+use anyhow::Result;
+use nalgebra_glm as glm;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    aabb::Aabb,
+    block::Block,
+    fluid::{flow, FluidSchedule},
+    light::{light_chunk, update_light, LightChannel},
+    registry::BlockRegistry,
+    terrain::{NoiseTerrainGenerator, TerrainGenerator},
+};
+
+pub const CHUNK_WIDTH: usize = 16;
+pub const CHUNK_LENGTH: usize = 16;
+pub const CHUNK_DEPTH: usize = 64;
+
+const VIEW_DISTANCE: i32 = 6;
+/// How many unloaded chunks with edits are kept in memory
+/// before they're written to the world's storage
+const MAX_STORED_CHUNKS: usize = 256;
+const WORKER_THREADS: usize = 2;
+
+/// Offsets to the six blocks sharing a face with a block
+pub const NEIGHBORS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [1, 0, 0],
+    [0, -1, 0],
+    [0, 1, 0],
+    [0, 0, -1],
+    [0, 0, 1],
+];
+
+/// Chunk coordinates along the world's x and z axes, in units of chunks
+pub type ChunkPosition = glm::IVec2;
+
+/// Keeps the chunks that were changed from how the terrain generator made them,
+/// so they can be loaded again in place of generating them
+pub trait ChunkStorage: Send + Sync {
+    /// Reads the stored chunk at a position, if there is one
+    fn load(&self, position: &ChunkPosition) -> Result<Option<Chunk>>;
+
+    /// Writes chunks, replacing any stored at the same positions
+    fn store(&self, chunks: &[(ChunkPosition, &Chunk)]) -> Result<()>;
+}
+
+pub struct World {
+    pub seed: u64,
+    pub registry: Arc<BlockRegistry>,
+    pub chunks: HashMap<ChunkPosition, Chunk>,
+    pub view_distance: i32,
+    /// Modified chunks that were unloaded, kept so they aren't regenerated.
+    /// Without storage to write them to, they're kept for as long as the world is.
+    stored_chunks: HashMap<ChunkPosition, Chunk>,
+    storage: Option<Arc<dyn ChunkStorage>>,
+    dirty_chunks: HashSet<ChunkPosition>,
+    streamer: ChunkStreamer,
+    /// Simulation steps run so far, which scheduled updates are timed by
+    ticks: u64,
+    fluid_schedule: FluidSchedule,
+}
+
+impl World {
+    pub fn new(seed: u64, registry: Arc<BlockRegistry>) -> Self {
+        Self::with_generator(seed, registry, Arc::new(NoiseTerrainGenerator::new(seed)))
+    }
+
+    pub fn with_generator(
+        seed: u64,
+        registry: Arc<BlockRegistry>,
+        generator: Arc<dyn TerrainGenerator>,
+    ) -> Self {
+        Self::create(seed, registry, generator, None)
+    }
+
+    /// Creates a world that loads chunks from storage before generating them
+    pub fn with_storage(
+        seed: u64,
+        registry: Arc<BlockRegistry>,
+        generator: Arc<dyn TerrainGenerator>,
+        storage: Arc<dyn ChunkStorage>,
+    ) -> Self {
+        Self::create(seed, registry, generator, Some(storage))
+    }
+
+    fn create(
+        seed: u64,
+        registry: Arc<BlockRegistry>,
+        generator: Arc<dyn TerrainGenerator>,
+        storage: Option<Arc<dyn ChunkStorage>>,
+    ) -> Self {
+        Self {
+            seed,
+            registry,
+            chunks: HashMap::new(),
+            view_distance: VIEW_DISTANCE,
+            stored_chunks: HashMap::new(),
+            dirty_chunks: HashSet::new(),
+            streamer: ChunkStreamer::new(generator, storage.clone(), WORKER_THREADS),
+            storage,
+            ticks: 0,
+            fluid_schedule: FluidSchedule::default(),
+        }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Advances the simulation by one step, letting the fluids that are due flow
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        for position in self.fluid_schedule.take_due(self.ticks) {
+            flow(self, &position);
+        }
+    }
+
+    /// Requests chunks within the view distance of the camera,
+    /// unloads chunks outside of it and takes in any chunks
+    /// the workers have finished generating.
+    pub fn update(&mut self, camera_position: &glm::Vec3) {
+        let center = chunk_position_of(&block_position_of(camera_position));
+        let nearby = chunks_in_radius(&center, self.view_distance);
+        let wanted = nearby.iter().copied().collect::<HashSet<_>>();
+        self.streamer.set_wanted(&wanted);
+
+        let unwanted = self
+            .chunks
+            .keys()
+            .filter(|position| !wanted.contains(position))
+            .cloned()
+            .collect::<Vec<_>>();
+        for position in unwanted {
+            self.unload_chunk(&position);
+        }
+
+        for position in nearby {
+            if self.chunks.contains_key(&position) {
+                continue;
+            }
+            match self.stored_chunks.remove(&position) {
+                Some(chunk) => self.insert_chunk(position, chunk),
+                None => self.streamer.request(&position),
+            }
+        }
+
+        for (position, chunk) in self.streamer.finished_chunks() {
+            if !self.chunks.contains_key(&position) {
+                self.insert_chunk(position, chunk);
+            }
+        }
+    }
+
+    /// Blocks until every requested chunk has been generated and inserted
+    pub fn finish_loading(&mut self) {
+        for (position, chunk) in self.streamer.wait_for_pending() {
+            if !self.chunks.contains_key(&position) {
+                self.insert_chunk(position, chunk);
+            }
+        }
+    }
+
+    pub fn insert_chunk(&mut self, position: ChunkPosition, chunk: Chunk) {
+        let origin = chunk.origin();
+        let mut fluids = Vec::new();
+        for (x, columns) in chunk.blocks.iter().enumerate() {
+            for (z, column) in columns.iter().enumerate() {
+                for (y, block) in column.iter().enumerate() {
+                    if block.fluid().is_some() {
+                        fluids.push(origin + glm::vec3(x as i32, y as i32, z as i32));
+                    }
+                }
+            }
+        }
+        self.chunks.insert(position, chunk);
+        // Fluids in saved chunks may have stopped partway through flowing
+        for fluid in fluids.iter() {
+            self.schedule_fluid(fluid);
+        }
+        light_chunk(self, &position);
+        self.dirty_chunks.insert(position);
+        self.mark_neighbors_dirty(&position);
+    }
+
+    pub fn unload_chunk(&mut self, position: &ChunkPosition) {
+        if let Some(chunk) = self.chunks.remove(position) {
+            if chunk.modified {
+                self.stored_chunks.insert(*position, chunk);
+                if self.stored_chunks.len() > MAX_STORED_CHUNKS {
+                    self.write_stored_chunks();
+                }
+            }
+            self.mark_neighbors_dirty(position);
+        }
+    }
+
+    /// Moves the unloaded chunks kept in memory to storage.
+    /// They stay in memory if there's no storage or writing them fails.
+    fn write_stored_chunks(&mut self) {
+        let storage = match self.storage.as_ref() {
+            Some(storage) => storage,
+            None => return,
+        };
+        let chunks = self
+            .stored_chunks
+            .iter()
+            .map(|(position, chunk)| (*position, chunk))
+            .collect::<Vec<_>>();
+        match storage.store(&chunks) {
+            Ok(()) => self.stored_chunks.clear(),
+            Err(error) => eprintln!("{:#}\nKeeping unloaded chunks in memory", error),
+        }
+    }
+
+    /// Returns every chunk held in memory, both loaded and unloaded ones kept for their edits
+    pub fn all_chunks(&self) -> impl Iterator<Item = (&ChunkPosition, &Chunk)> {
+        self.chunks.iter().chain(self.stored_chunks.iter())
+    }
+
+    /// Returns the chunks whose meshes need rebuilding since the last call
+    pub fn take_dirty_chunks(&mut self) -> HashSet<ChunkPosition> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
+
+    /// Returns the chunk containing a world position, if it has been loaded
+    pub fn chunk_at(&self, world_position: &glm::IVec3) -> Option<&Chunk> {
+        self.chunks.get(&chunk_position_of(world_position))
+    }
+
+    /// Returns the block at a world position, or None if no chunk contains it
+    pub fn block_at(&self, world_position: &glm::IVec3) -> Option<Block> {
+        let chunk = self.chunk_at(world_position)?;
+        chunk.get_block(&(world_position - chunk.origin()))
+    }
+
+    /// Replaces the block at a world position, returning false if no chunk contains it
+    pub fn set_block(&mut self, world_position: &glm::IVec3, block: Block) -> bool {
+        let chunk_position = chunk_position_of(world_position);
+        let chunk = match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+
+        let local = world_position - chunk.origin();
+        if chunk.get_block(&local).is_none() {
+            return false;
+        }
+        let previous = chunk.block(&local);
+        chunk.blocks[local.x as usize][local.z as usize][local.y as usize] = block;
+        chunk.modified = true;
+
+        self.mark_block_dirty(&chunk_position, &local);
+        update_light(self, world_position, previous);
+        self.schedule_fluid(world_position);
+        for direction in NEIGHBORS.iter() {
+            self.schedule_fluid(&(world_position + glm::IVec3::from(*direction)));
+        }
+        true
+    }
+
+    /// Schedules the fluid at a world position to flow after its delay, if there is one
+    fn schedule_fluid(&mut self, world_position: &glm::IVec3) {
+        if let Some((fluid, _)) = self
+            .block_at(world_position)
+            .and_then(|block| block.fluid())
+        {
+            self.fluid_schedule
+                .schedule(world_position, self.ticks + fluid.flow_delay());
+        }
+    }
+
+    /// Returns the light level at a world position, or None if no chunk contains it
+    pub fn light_at(&self, world_position: &glm::IVec3, channel: LightChannel) -> Option<u8> {
+        let chunk = self.chunk_at(world_position)?;
+        let local = world_position - chunk.origin();
+        chunk.get_block(&local)?;
+        Some(chunk.light(&local, channel))
+    }
+
+    /// Changes the light level at a world position, marking the affected meshes for rebuilding
+    pub fn set_light(&mut self, world_position: &glm::IVec3, channel: LightChannel, level: u8) {
+        let chunk_position = chunk_position_of(world_position);
+        let chunk = match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let local = world_position - chunk.origin();
+        if chunk.get_block(&local).is_none() || chunk.light(&local, channel) == level {
+            return;
+        }
+        chunk.set_light(&local, channel, level);
+        self.mark_block_dirty(&chunk_position, &local);
+    }
+
+    /// Blocks on the chunk border affect the faces of the neighboring chunk
+    fn mark_block_dirty(&mut self, chunk_position: &ChunkPosition, local: &glm::IVec3) {
+        self.dirty_chunks.insert(*chunk_position);
+        let borders = [
+            (local.x == 0, glm::vec2(-1, 0)),
+            (local.x == CHUNK_WIDTH as i32 - 1, glm::vec2(1, 0)),
+            (local.z == 0, glm::vec2(0, -1)),
+            (local.z == CHUNK_LENGTH as i32 - 1, glm::vec2(0, 1)),
+        ];
+        for (on_border, offset) in borders.iter() {
+            let neighbor = chunk_position + offset;
+            if *on_border && self.chunks.contains_key(&neighbor) {
+                self.dirty_chunks.insert(neighbor);
+            }
+        }
+    }
+
+    fn mark_neighbors_dirty(&mut self, position: &ChunkPosition) {
+        for offset in [
+            glm::vec2(-1, 0),
+            glm::vec2(1, 0),
+            glm::vec2(0, -1),
+            glm::vec2(0, 1),
+        ]
+        .iter()
+        {
+            let neighbor = position + offset;
+            if self.chunks.contains_key(&neighbor) {
+                self.dirty_chunks.insert(neighbor);
+            }
+        }
+    }
+}
+
+/// A world of the blocks in assets/blocks.ron with terrain from a stub generator,
+/// for tests. No chunks are loaded until they're asked for.
+#[cfg(test)]
+pub fn test_world(generator: impl TerrainGenerator + 'static) -> World {
+    use crate::texture_pack::{TexturePack, DEFAULT_PACK};
+
+    let tiles = TexturePack::load(DEFAULT_PACK).unwrap();
+    let registry = BlockRegistry::load("assets/blocks.ron", &tiles).unwrap();
+    World::with_generator(0, Arc::new(registry), Arc::new(generator))
+}
+
+/// Returns the position of the block containing a point in world space
+pub fn block_position_of(position: &glm::Vec3) -> glm::IVec3 {
+    glm::vec3(
+        position.x.floor() as i32,
+        position.y.floor() as i32,
+        position.z.floor() as i32,
+    )
+}
+
+/// Returns the position of the chunk containing a block
+pub fn chunk_position_of(world_position: &glm::IVec3) -> ChunkPosition {
+    glm::vec2(
+        world_position.x.div_euclid(CHUNK_WIDTH as i32),
+        world_position.z.div_euclid(CHUNK_LENGTH as i32),
+    )
+}
+
+/// Returns the chunk positions within a circular radius of a center chunk, nearest first
+pub fn chunks_in_radius(center: &ChunkPosition, radius: i32) -> Vec<ChunkPosition> {
+    let mut positions = Vec::new();
+    for z in -radius..=radius {
+        for x in -radius..=radius {
+            if x * x + z * z <= radius * radius {
+                positions.push(center + glm::vec2(x, z));
+            }
+        }
+    }
+    positions.sort_by_key(|position| {
+        let offset = position - center;
+        offset.x * offset.x + offset.y * offset.y
+    });
+    positions
+}
+
+/// Loads chunks from storage or generates them on a pool of worker threads
+struct ChunkStreamer {
+    requests: Option<Sender<ChunkPosition>>,
+    finished: Receiver<(ChunkPosition, Chunk)>,
+    pending: HashSet<ChunkPosition>,
+    /// The chunks the world still wants. Workers skip requests for any others,
+    /// so chunks left behind by a moving camera don't hold up the ones near it.
+    wanted: Arc<Mutex<HashSet<ChunkPosition>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ChunkStreamer {
+    fn new(
+        generator: Arc<dyn TerrainGenerator>,
+        storage: Option<Arc<dyn ChunkStorage>>,
+        number_of_workers: usize,
+    ) -> Self {
+        let (request_sender, request_receiver) = mpsc::channel::<ChunkPosition>();
+        let (finished_sender, finished_receiver) = mpsc::channel();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+        let wanted = Arc::new(Mutex::new(HashSet::new()));
+
+        let workers = (0..number_of_workers)
+            .map(|_| {
+                let requests = request_receiver.clone();
+                let finished = finished_sender.clone();
+                let generator = generator.clone();
+                let storage = storage.clone();
+                let wanted = wanted.clone();
+                thread::spawn(move || loop {
+                    let request = match requests.lock() {
+                        Ok(requests) => requests.recv(),
+                        Err(_) => return,
+                    };
+                    let position = match request {
+                        Ok(position) => position,
+                        Err(_) => return,
+                    };
+                    if !is_wanted(&wanted, &position) {
+                        continue;
+                    }
+                    let stored = match storage.as_ref().map(|storage| storage.load(&position)) {
+                        Some(Ok(chunk)) => chunk,
+                        Some(Err(error)) => {
+                            eprintln!("{:#}\nGenerating the chunk instead", error);
+                            None
+                        }
+                        None => None,
+                    };
+                    let chunk = stored.unwrap_or_else(|| generator.generate(&position));
+                    if finished.send((position, chunk)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            requests: Some(request_sender),
+            finished: finished_receiver,
+            pending: HashSet::new(),
+            wanted,
+            workers,
+        }
+    }
+
+    /// Replaces the chunks the world wants, dropping pending requests for any others
+    fn set_wanted(&mut self, wanted: &HashSet<ChunkPosition>) {
+        if let Ok(mut shared) = self.wanted.lock() {
+            shared.clone_from(wanted);
+        }
+        self.pending.retain(|position| wanted.contains(position));
+    }
+
+    fn request(&mut self, position: &ChunkPosition) {
+        if self.pending.contains(position) {
+            return;
+        }
+        if let Some(requests) = self.requests.as_ref() {
+            if requests.send(*position).is_ok() {
+                self.pending.insert(*position);
+            }
+        }
+    }
+
+    /// Takes the chunks that finished generating and are still wanted
+    fn finished_chunks(&mut self) -> Vec<(ChunkPosition, Chunk)> {
+        let chunks = self.finished.try_iter().collect::<Vec<_>>();
+        for (position, _) in chunks.iter() {
+            self.pending.remove(position);
+        }
+        chunks
+            .into_iter()
+            .filter(|(position, _)| is_wanted(&self.wanted, position))
+            .collect()
+    }
+
+    fn wait_for_pending(&mut self) -> Vec<(ChunkPosition, Chunk)> {
+        let mut chunks = Vec::new();
+        while !self.pending.is_empty() {
+            match self.finished.recv() {
+                Ok((position, chunk)) => {
+                    self.pending.remove(&position);
+                    if is_wanted(&self.wanted, &position) {
+                        chunks.push((position, chunk));
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        chunks
+    }
+}
+
+fn is_wanted(wanted: &Mutex<HashSet<ChunkPosition>>, position: &ChunkPosition) -> bool {
+    match wanted.lock() {
+        Ok(wanted) => wanted.contains(position),
+        Err(_) => false,
+    }
+}
+
+impl Drop for ChunkStreamer {
+    fn drop(&mut self) {
+        // Closing the request channel lets the workers exit their loops
+        self.requests.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub struct Chunk {
+    pub position: glm::Vec3,
+    pub blocks: [[[Block; CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH],
+    /// Set when the chunk's blocks are edited after it was generated or loaded from storage.
+    /// Modified chunks are kept when unloaded, and written out when the world is saved.
+    pub modified: bool,
+    /// Sky light in the high four bits and block light in the low four bits,
+    /// computed when the chunk is added to a world
+    pub light: [[[u8; CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH],
+}
+
+impl Chunk {
+    pub fn new(position: &ChunkPosition) -> Self {
+        Self {
+            position: glm::vec3(
+                (position.x * CHUNK_WIDTH as i32) as f32,
+                0.0,
+                (position.y * CHUNK_LENGTH as i32) as f32,
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// The world position of the chunk's first block
+    pub fn origin(&self) -> glm::IVec3 {
+        glm::vec3(
+            self.position.x as i32,
+            self.position.y as i32,
+            self.position.z as i32,
+        )
+    }
+
+    /// The box in world space containing all of the chunk's blocks
+    pub fn bounding_box(&self) -> Aabb {
+        Aabb {
+            min: self.position,
+            max: self.position
+                + glm::vec3(CHUNK_WIDTH as f32, CHUNK_DEPTH as f32, CHUNK_LENGTH as f32),
+        }
+    }
+
+    /// Returns the block at a position local to the chunk
+    pub fn block(&self, position: &glm::IVec3) -> Block {
+        self.blocks[position.x as usize][position.z as usize][position.y as usize]
+    }
+
+    /// Returns the block at a local position, or None if it lies outside the chunk
+    pub fn get_block(&self, position: &glm::IVec3) -> Option<Block> {
+        let inside = (0..CHUNK_WIDTH as i32).contains(&position.x)
+            && (0..CHUNK_DEPTH as i32).contains(&position.y)
+            && (0..CHUNK_LENGTH as i32).contains(&position.z);
+        if inside {
+            Some(self.block(position))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the light level of a channel at a position local to the chunk
+    pub fn light(&self, position: &glm::IVec3, channel: LightChannel) -> u8 {
+        let packed = self.light[position.x as usize][position.z as usize][position.y as usize];
+        match channel {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0x0f,
+        }
+    }
+
+    pub fn set_light(&mut self, position: &glm::IVec3, channel: LightChannel, level: u8) {
+        let packed = &mut self.light[position.x as usize][position.z as usize][position.y as usize];
+        let level = level.min(0x0f);
+        *packed = match channel {
+            LightChannel::Sky => (*packed & 0x0f) | (level << 4),
+            LightChannel::Block => (*packed & 0xf0) | level,
+        };
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        let blocks = [[[Block::default(); CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH];
+        Self {
+            position: glm::vec3(0.0, 0.0, 0.0),
+            blocks,
+            modified: false,
+            light: [[[0; CHUNK_DEPTH]; CHUNK_LENGTH]; CHUNK_WIDTH],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::FlatTerrainGenerator;
+
+    fn loaded(world: &World) -> HashSet<ChunkPosition> {
+        world.chunks.keys().copied().collect()
+    }
+
+    fn around(center: &ChunkPosition, radius: i32) -> HashSet<ChunkPosition> {
+        chunks_in_radius(center, radius).into_iter().collect()
+    }
+
+    /// A point above the middle of a chunk
+    fn above_chunk(position: &ChunkPosition) -> glm::Vec3 {
+        glm::vec3(
+            (position.x * CHUNK_WIDTH as i32) as f32 + 8.0,
+            30.0,
+            (position.y * CHUNK_LENGTH as i32) as f32 + 8.0,
+        )
+    }
+
+    #[test]
+    fn loads_the_chunks_around_a_moving_camera() {
+        let mut world = test_world(FlatTerrainGenerator { height: 4 });
+        world.view_distance = 2;
+        world.update(&above_chunk(&glm::vec2(0, 0)));
+        world.finish_loading();
+        assert_eq!(loaded(&world), around(&glm::vec2(0, 0), 2));
+
+        world.update(&above_chunk(&glm::vec2(2, -1)));
+        world.finish_loading();
+        assert_eq!(loaded(&world), around(&glm::vec2(2, -1), 2));
+    }
+
+    #[test]
+    fn drops_requests_the_camera_left_behind() {
+        let mut world = test_world(FlatTerrainGenerator { height: 4 });
+        world.view_distance = 3;
+        world.update(&above_chunk(&glm::vec2(0, 0)));
+        world.update(&above_chunk(&glm::vec2(20, 0)));
+        world.finish_loading();
+        assert_eq!(loaded(&world), around(&glm::vec2(20, 0), 3));
+        assert!(world.streamer.pending.is_empty());
+    }
+
+    /// Keeps stored chunks' blocks in memory
+    #[derive(Default)]
+    struct MemoryStorage {
+        chunks: Mutex<HashMap<ChunkPosition, Chunk>>,
+    }
+
+    impl ChunkStorage for MemoryStorage {
+        fn load(&self, position: &ChunkPosition) -> Result<Option<Chunk>> {
+            let chunks = self.chunks.lock().unwrap();
+            Ok(chunks.get(position).map(|stored| {
+                let mut chunk = Chunk::new(position);
+                chunk.blocks = stored.blocks;
+                chunk
+            }))
+        }
+
+        fn store(&self, chunks: &[(ChunkPosition, &Chunk)]) -> Result<()> {
+            let mut stored = self.chunks.lock().unwrap();
+            for (position, chunk) in chunks.iter() {
+                let mut copy = Chunk::new(position);
+                copy.blocks = chunk.blocks;
+                stored.insert(*position, copy);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn unloaded_edits_are_moved_to_storage() {
+        let storage = Arc::new(MemoryStorage::default());
+        let mut world = World::with_storage(
+            0,
+            test_world(FlatTerrainGenerator { height: 0 }).registry,
+            Arc::new(FlatTerrainGenerator { height: 4 }),
+            storage.clone(),
+        );
+        let positions = (0..=MAX_STORED_CHUNKS as i32)
+            .map(|x| glm::vec2(x, 100))
+            .collect::<Vec<_>>();
+        for (index, position) in positions.iter().enumerate() {
+            // Chunk::new is full of dirt, unlike the generated chunks
+            let mut chunk = Chunk::new(position);
+            chunk.blocks[0][0][10] = Block::Cobblestone;
+            chunk.modified = true;
+            world.chunks.insert(*position, chunk);
+            world.unload_chunk(position);
+            if index < MAX_STORED_CHUNKS {
+                assert_eq!(world.stored_chunks.len(), index + 1);
+                assert!(storage.chunks.lock().unwrap().is_empty());
+            }
+        }
+        assert!(world.stored_chunks.is_empty());
+        assert_eq!(storage.chunks.lock().unwrap().len(), MAX_STORED_CHUNKS + 1);
+
+        // Stored chunks stream back in with their edits instead of being generated
+        world.view_distance = 0;
+        world.update(&above_chunk(&positions[5]));
+        world.finish_loading();
+        let origin = world.chunks[&positions[5]].origin();
+        assert_eq!(
+            world.block_at(&(origin + glm::vec3(0, 10, 0))),
+            Some(Block::Cobblestone)
+        );
+        assert_eq!(
+            world.block_at(&(origin + glm::vec3(1, 20, 0))),
+            Some(Block::Dirt)
+        );
+    }
+
+    #[test]
+    fn chunks_in_radius_are_nearest_first() {
+        let center = glm::vec2(3, -2);
+        let positions = chunks_in_radius(&center, 4);
+        assert_eq!(positions[0], center);
+        let distances = positions
+            .iter()
+            .map(|position| (position - center).map(|offset| offset * offset).sum())
+            .collect::<Vec<i32>>();
+        assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(distances.iter().all(|distance| *distance <= 16));
+    }
+}